@@ -0,0 +1,61 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Times creating many temporary directories in a row.
+//!
+//! Run with `cargo bench`, or with
+//! `cargo bench --no-default-features --features minimal-rng` for the
+//! `getrandom` backend. `TempDir::new_in` sets up a fresh builder, and
+//! so a fresh generator, for every directory, while the
+//! `TempDirBuilder::tempdir_in` line reuses one builder throughout.
+
+extern crate tempdir;
+
+use std::time::{Duration, Instant};
+
+use tempdir::{TempDir, TempDirBuilder};
+
+const COUNT: u32 = 10_000;
+
+fn report(what: &str, elapsed: Duration) {
+    let per_op = elapsed.as_secs_f64() * 1e9 / f64::from(COUNT);
+    println!("{:<28} {:>8.0} ns/op ({} ops in {:?})", what, per_op, COUNT, elapsed);
+}
+
+fn main() {
+    let start = Instant::now();
+    for _ in 0..COUNT {
+        tempdir::random_name("bench", 12);
+    }
+    report("random_name", start.elapsed());
+
+    let base = TempDir::new("bench").expect("failed to create the base directory");
+
+    let mut dirs = Vec::with_capacity(COUNT as usize);
+    let start = Instant::now();
+    for _ in 0..COUNT {
+        dirs.push(TempDir::new_in(base.path(), "bench").expect("failed to create a directory"));
+    }
+    report("TempDir::new_in", start.elapsed());
+
+    let start = Instant::now();
+    drop(dirs);
+    report("drop", start.elapsed());
+
+    let mut builder = TempDirBuilder::new();
+    builder.prefix("bench");
+
+    let mut dirs = Vec::with_capacity(COUNT as usize);
+    let start = Instant::now();
+    for _ in 0..COUNT {
+        dirs.push(builder.tempdir_in(base.path()).expect("failed to create a directory"));
+    }
+    report("TempDirBuilder::tempdir_in", start.elapsed());
+}
@@ -0,0 +1,97 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Restricting a directory to the current user on Windows.
+
+use std::io;
+use std::mem;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+use winapi::shared::winerror::ERROR_SUCCESS;
+use winapi::um::accctrl::{EXPLICIT_ACCESS_W, NO_MULTIPLE_TRUSTEE, SET_ACCESS, SE_FILE_OBJECT,
+                          SUB_CONTAINERS_AND_OBJECTS_INHERIT, TRUSTEE_IS_SID, TRUSTEE_IS_USER,
+                          TRUSTEE_W};
+use winapi::um::aclapi::{SetEntriesInAclW, SetNamedSecurityInfoW};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+use winapi::um::securitybaseapi::GetTokenInformation;
+use winapi::um::winbase::LocalFree;
+use winapi::um::winnt::{TokenUser, DACL_SECURITY_INFORMATION, GENERIC_ALL, HANDLE, PACL,
+                        PROTECTED_DACL_SECURITY_INFORMATION, TOKEN_QUERY, TOKEN_USER};
+
+/// Replaces the DACL of `path` with one granting full control to the
+/// current user and nobody else, inherited by everything created inside.
+///
+/// The user's SID comes from the process token (`OpenProcessToken` and
+/// `GetTokenInformation`), the ACL is built with `SetEntriesInAclW` and
+/// applied with `SetNamedSecurityInfoW`. The DACL is marked protected, so
+/// entries inherited from the parent directory are dropped.
+pub fn restrict_to_user(path: &Path) -> io::Result<()> {
+    unsafe {
+        let mut token: HANDLE = ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = restrict_with_token(path, token);
+        CloseHandle(token);
+        result
+    }
+}
+
+unsafe fn restrict_with_token(path: &Path, token: HANDLE) -> io::Result<()> {
+    // The first call only reports the size of the `TOKEN_USER`, which is
+    // followed by the SID it points to.
+    let mut len = 0;
+    GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len);
+    if len == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // `u64`s keep the buffer aligned for `TOKEN_USER`.
+    let mut buf = vec![0u64; (len as usize).div_ceil(mem::size_of::<u64>())];
+    if GetTokenInformation(token, TokenUser, buf.as_mut_ptr() as *mut _, len, &mut len) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let user = &*(buf.as_ptr() as *const TOKEN_USER);
+
+    let mut access = EXPLICIT_ACCESS_W {
+        grfAccessPermissions: GENERIC_ALL,
+        grfAccessMode: SET_ACCESS,
+        grfInheritance: SUB_CONTAINERS_AND_OBJECTS_INHERIT,
+        Trustee: TRUSTEE_W {
+            pMultipleTrustee: ptr::null_mut(),
+            MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+            TrusteeForm: TRUSTEE_IS_SID,
+            TrusteeType: TRUSTEE_IS_USER,
+            ptstrName: user.User.Sid as *mut _,
+        },
+    };
+    let mut acl: PACL = ptr::null_mut();
+    let err = SetEntriesInAclW(1, &mut access, ptr::null_mut(), &mut acl);
+    if err != ERROR_SUCCESS {
+        return Err(io::Error::from_raw_os_error(err as i32));
+    }
+
+    let mut wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let err = SetNamedSecurityInfoW(wide.as_mut_ptr(),
+                                    SE_FILE_OBJECT,
+                                    DACL_SECURITY_INFORMATION |
+                                    PROTECTED_DACL_SECURITY_INFORMATION,
+                                    ptr::null_mut(),
+                                    ptr::null_mut(),
+                                    acl,
+                                    ptr::null_mut());
+    LocalFree(acl as *mut _);
+    if err != ERROR_SUCCESS {
+        return Err(io::Error::from_raw_os_error(err as i32));
+    }
+    Ok(())
+}
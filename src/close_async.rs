@@ -0,0 +1,80 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::future::Future;
+use std::io::{self, Error};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::task::{self, JoinHandle};
+
+use TempDir;
+
+/// The future returned by [`TempDir::close_async`].
+///
+/// The directory is only handed to the blocking thread pool when the
+/// future is first polled. If the future is dropped before that, the
+/// `TempDir` it holds is dropped with it and removes the directory as
+/// usual.
+///
+/// [`TempDir::close_async`]: struct.TempDir.html#method.close_async
+#[must_use = "futures do nothing unless polled"]
+pub struct CloseAsync {
+    state: State,
+}
+
+enum State {
+    Idle(Option<TempDir>),
+    Removing(JoinHandle<io::Result<()>>),
+}
+
+impl CloseAsync {
+    pub(crate) fn new(dir: TempDir) -> CloseAsync {
+        CloseAsync {
+            state: State::Idle(Some(dir)),
+        }
+    }
+}
+
+impl Future for CloseAsync {
+    type Output = io::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        if let State::Idle(ref mut dir) = self.state {
+            // Only taken once the task exists, so nothing is lost if the
+            // future is dropped before this.
+            let dir = dir.take().expect("polled after completion");
+            let handle = task::spawn_blocking(move || dir.close());
+            self.state = State::Removing(handle);
+        }
+
+        match self.state {
+            State::Removing(ref mut handle) => match Pin::new(handle).poll(cx) {
+                Poll::Ready(Ok(result)) => Poll::Ready(result),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(Error::other(e))),
+                Poll::Pending => Poll::Pending,
+            },
+            State::Idle(_) => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Debug for CloseAsync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.state {
+            State::Idle(_) => "idle",
+            State::Removing(_) => "removing",
+        };
+        f.debug_struct("CloseAsync")
+            .field("state", &state)
+            .finish()
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::env;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use TempDir;
+
+/// A guard that restores the previous current directory when dropped.
+///
+/// This is returned by [`TempDir::enter`]. It borrows the `TempDir` it
+/// was created from, so the directory can't be deleted while it is
+/// still the current directory.
+///
+/// The current directory is shared by every thread in the process, so
+/// code running on other threads is affected as well, and guards from
+/// different threads must not overlap.
+///
+/// [`TempDir::enter`]: struct.TempDir.html#method.enter
+pub struct CwdGuard<'a> {
+    previous: PathBuf,
+    _dir: PhantomData<&'a TempDir>,
+}
+
+impl<'a> CwdGuard<'a> {
+    pub(crate) fn enter(dir: &'a TempDir) -> io::Result<CwdGuard<'a>> {
+        let previous = env::current_dir()?;
+        env::set_current_dir(dir.path())?;
+        Ok(CwdGuard {
+            previous,
+            _dir: PhantomData,
+        })
+    }
+
+    /// Returns the current directory from before the guard was created,
+    /// which will be restored when it is dropped.
+    pub fn previous(&self) -> &Path {
+        &self.previous
+    }
+}
+
+impl<'a> fmt::Debug for CwdGuard<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CwdGuard")
+            .field("previous", &self.previous)
+            .finish()
+    }
+}
+
+impl<'a> Drop for CwdGuard<'a> {
+    fn drop(&mut self) {
+        let _ = env::set_current_dir(&self.previous);
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A stable digest of a directory tree.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use walk::walk;
+
+// 64-bit FNV-1a. Unlike `DefaultHasher`, its output is fixed by its
+// definition, so digests can be compared across runs, platforms and
+// compiler versions.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Writes `bytes` preceded by their length, so that consecutive
+    // fields can't run into each other.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+/// Hashes the relative paths, types and contents of everything below
+/// `root`, visiting entries in sorted path order.
+///
+/// Symbolic links contribute their target path and are not followed.
+/// Trees deeper than `max_depth` fail as in `walk`.
+pub fn content_hash(root: &Path, max_depth: usize) -> io::Result<u64> {
+    let mut entries: Vec<(PathBuf, fs::FileType)> = Vec::new();
+    walk(root, max_depth, |path, metadata| {
+        // Every walked path is below `root`.
+        let rel = path.strip_prefix(root).unwrap().to_path_buf();
+        entries.push((rel, metadata.file_type()));
+        Ok(())
+    })?;
+    // `Path` orders component by component, so each directory comes right
+    // before its contents regardless of the separator.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hasher = Fnv1a::new();
+    for (rel, file_type) in entries {
+        // Components are joined with `/` on every platform.
+        let mut name = Vec::new();
+        for component in rel.components() {
+            if let Component::Normal(part) = component {
+                if !name.is_empty() {
+                    name.push(b'/');
+                }
+                name.extend_from_slice(&os_bytes(part));
+            }
+        }
+        hasher.write_field(&name);
+
+        let path = root.join(&rel);
+        if file_type.is_symlink() {
+            hasher.write(b"l");
+            hasher.write_field(&os_bytes(fs::read_link(&path)?.as_os_str()));
+        } else if file_type.is_dir() {
+            hasher.write(b"d");
+        } else {
+            hasher.write(b"f");
+            hasher.write_field(&fs::read(&path)?);
+        }
+    }
+    Ok(hasher.0)
+}
+
+#[cfg(unix)]
+fn os_bytes(s: &::std::ffi::OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    s.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(s: &::std::ffi::OsStr) -> Vec<u8> {
+    s.to_string_lossy().into_owned().into_bytes()
+}
@@ -0,0 +1,95 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+/// The error returned by the typed constructors such as
+/// [`TempDir::try_new`] and [`TempDirBuilder::try_tempdir`].
+///
+/// The `io::Result` constructors report the same failures as an
+/// `io::Error`: exhaustion with `ErrorKind::AlreadyExists` and invalid
+/// names with `ErrorKind::InvalidInput`. This type tells them apart from
+/// other I/O errors without inspecting the error kind or message, and
+/// converts into the equivalent `io::Error` with `From`.
+///
+/// More variants may be added in the future, so matches on
+/// `TempDirError` must include a wildcard arm.
+///
+/// [`TempDir::try_new`]: struct.TempDir.html#method.try_new
+/// [`TempDirBuilder::try_tempdir`]: struct.TempDirBuilder.html#method.try_tempdir
+///
+/// # Examples
+///
+/// ```
+/// use tempdir::{TempDirBuilder, TempDirError};
+///
+/// let result = TempDirBuilder::new().prefix("example").max_retries(0).try_tempdir();
+/// match result {
+///     Err(TempDirError::Exhausted { attempts }) => assert_eq!(attempts, 0),
+///     _ => panic!("expected the name search to be exhausted"),
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TempDirError {
+    /// An I/O error other than a name collision, such as the base
+    /// directory not existing or permission being denied.
+    Io(io::Error),
+    /// Every name tried already existed.
+    Exhausted {
+        /// How many names were tried before giving up.
+        attempts: u32,
+    },
+    /// The configured prefix, suffix, separator or random alphabet can not
+    /// form a single valid file name. The message describes why.
+    InvalidName(String),
+}
+
+impl fmt::Display for TempDirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TempDirError::Io(ref e) => e.fmt(f),
+            TempDirError::Exhausted { attempts } => {
+                write!(f,
+                       "too many temporary directories already exist \
+                        (gave up after {} attempts)",
+                       attempts)
+            }
+            TempDirError::InvalidName(ref msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl error::Error for TempDirError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            TempDirError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TempDirError {
+    fn from(e: io::Error) -> TempDirError {
+        TempDirError::Io(e)
+    }
+}
+
+impl From<TempDirError> for io::Error {
+    fn from(e: TempDirError) -> io::Error {
+        match e {
+            TempDirError::Io(e) => e,
+            TempDirError::Exhausted { .. } => io::Error::new(ErrorKind::AlreadyExists, e.to_string()),
+            TempDirError::InvalidName(msg) => io::Error::new(ErrorKind::InvalidInput, msg),
+        }
+    }
+}
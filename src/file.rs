@@ -0,0 +1,185 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use random::Source;
+use TempDirError;
+use super::{check_name_part, create_unique, default_base, leaf_name, DEFAULT_RAND_CHARS,
+            NUM_RETRIES};
+
+/// A file in the filesystem that is automatically deleted when it goes
+/// out of scope.
+///
+/// `TempFile` is the single-file counterpart of [`TempDir`]: at
+/// construction it creates a new, empty file with a randomly generated
+/// name and a prefix of your choosing, and keeps it open for reading
+/// and writing. Once the `TempFile` value is dropped, the open handle
+/// is closed and the file is deleted. The destructor silently ignores
+/// any errors in deleting the file; to instead handle errors call
+/// [`TempFile::close`].
+///
+/// The file is always created as a new file, so an existing file is
+/// never opened or truncated by mistake.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`TempFile::close`]: struct.TempFile.html#method.close
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use tempdir::TempFile;
+///
+/// # use std::io;
+/// # fn run() -> Result<(), io::Error> {
+/// let mut tmp_file = TempFile::new("example")?;
+/// writeln!(tmp_file.as_file_mut(), "Brian was here. Briefly.")?;
+///
+/// // `tmp_file` goes out of scope and the file is deleted here.
+/// # Ok(())
+/// # }
+/// ```
+pub struct TempFile {
+    path: Option<PathBuf>,
+    file: Option<File>,
+}
+
+impl TempFile {
+    /// Attempts to make a temporary file inside of `env::temp_dir()` whose
+    /// name will have the prefix, `prefix`. The file will be automatically
+    /// deleted once the returned `TempFile` is destroyed.
+    ///
+    /// The base set with [`TempDir::set_default_base`] is used instead of
+    /// `env::temp_dir()` if there is one.
+    ///
+    /// [`TempDir::set_default_base`]: struct.TempDir.html#method.set_default_base
+    ///
+    /// # Errors
+    ///
+    /// If the file can not be created, `Err` is returned.
+    pub fn new(prefix: &str) -> io::Result<TempFile> {
+        TempFile::new_in(default_base(), prefix)
+    }
+
+    /// Attempts to make a temporary file inside of `tmpdir` whose name
+    /// will have the prefix, `prefix`. The file will be automatically
+    /// deleted once the returned `TempFile` is destroyed.
+    ///
+    /// # Errors
+    ///
+    /// If `prefix` contains a path separator or a NUL byte, an error
+    /// with `ErrorKind::InvalidInput` is returned. If every name tried
+    /// already exists, an error with `ErrorKind::AlreadyExists` giving
+    /// the number of attempts is returned. If the file can not be
+    /// created for any other reason, `Err` is returned.
+    pub fn new_in<P: AsRef<Path>>(tmpdir: P, prefix: &str) -> io::Result<TempFile> {
+        check_name_part(prefix, "prefix")?;
+
+        let storage;
+        let mut tmpdir = tmpdir.as_ref();
+        if !tmpdir.is_absolute() {
+            let cur_dir = env::current_dir()?;
+            storage = cur_dir.join(tmpdir);
+            tmpdir = &storage;
+        }
+
+        let mut rng = Source::thread();
+        let name = |_| Ok(leaf_name(prefix, Some('.'), &rng.chars(DEFAULT_RAND_CHARS), ""));
+        match create_unique(tmpdir, NUM_RETRIES, Duration::from_secs(0), name, create_new) {
+            Ok((path, file, _)) => Ok(TempFile { path: Some(path), file: Some(file) }),
+            Err(TempDirError::Exhausted { attempts }) => {
+                Err(Error::new(ErrorKind::AlreadyExists,
+                               format!("too many temporary files already exist \
+                                        (gave up after {} attempts)",
+                                       attempts)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Accesses the [`Path`] to the temporary file.
+    ///
+    /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
+    pub fn path(&self) -> &Path {
+        self.path.as_ref().unwrap()
+    }
+
+    /// Accesses the open [`File`] handle to the temporary file.
+    ///
+    /// [`File`]: http://doc.rust-lang.org/std/fs/struct.File.html
+    pub fn as_file(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+
+    /// Mutably accesses the open [`File`] handle to the temporary file.
+    ///
+    /// [`File`]: http://doc.rust-lang.org/std/fs/struct.File.html
+    pub fn as_file_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+
+    /// Closes and removes the temporary file, returning a `Result`.
+    ///
+    /// Although `TempFile` removes the file on drop, in the destructor
+    /// any errors are ignored. To detect errors cleaning up the
+    /// temporary file, call `close` instead.
+    pub fn close(mut self) -> io::Result<()> {
+        // Close the handle first, as Windows refuses to delete open files.
+        drop(self.file.take());
+        let result = fs::remove_file(self.path());
+
+        // Prevent the Drop impl from removing the file a second time.
+        self.path = None;
+
+        result
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl fmt::Debug for TempFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TempFile")
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        drop(self.file.take());
+        // Path is `None` if `close()` has been called.
+        if let Some(ref p) = self.path {
+            let _ = fs::remove_file(p);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_new(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_new(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).create_new(true).open(path)
+}
@@ -0,0 +1,124 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cell::OnceCell;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use {TempDir, TempDirBuilder};
+
+/// A temporary directory that is only created when it is first needed.
+///
+/// Constructing a `LazyTempDir` touches nothing on disk. The directory
+/// is created by the first call to [`ensure`] or [`path`], and later
+/// calls return the same directory. Once created it behaves like any
+/// other [`TempDir`] and is removed when the `LazyTempDir` is dropped; if
+/// it was never created, dropping does nothing.
+///
+/// A `LazyTempDir` can be sent to another thread but not shared between
+/// threads.
+///
+/// [`ensure`]: struct.LazyTempDir.html#method.ensure
+/// [`path`]: struct.LazyTempDir.html#method.path
+/// [`TempDir`]: struct.TempDir.html
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use tempdir::LazyTempDir;
+///
+/// # use std::io;
+/// # fn run() -> Result<(), io::Error> {
+/// let scratch = LazyTempDir::new("example");
+/// assert!(scratch.get().is_none());
+///
+/// let needs_scratch = true;
+/// if needs_scratch {
+///     File::create(scratch.path()?.join("spill.bin"))?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LazyTempDir {
+    builder: TempDirBuilder,
+    // `None` means the default base, looked up on creation.
+    tmpdir: Option<PathBuf>,
+    dir: OnceCell<TempDir>,
+}
+
+impl LazyTempDir {
+    /// Prepares a temporary directory that will be created inside of
+    /// `env::temp_dir()` with the prefix `prefix`, like [`TempDir::new`].
+    ///
+    /// [`TempDir::new`]: struct.TempDir.html#method.new
+    pub fn new(prefix: &str) -> LazyTempDir {
+        TempDirBuilder::new().prefix(prefix).lazy()
+    }
+
+    /// Prepares a temporary directory that will be created inside of
+    /// `tmpdir` with the prefix `prefix`, like [`TempDir::new_in`].
+    ///
+    /// [`TempDir::new_in`]: struct.TempDir.html#method.new_in
+    pub fn new_in<P: AsRef<Path>>(tmpdir: P, prefix: &str) -> LazyTempDir {
+        TempDirBuilder::new().prefix(prefix).lazy_in(tmpdir)
+    }
+
+    pub(crate) fn from_builder(builder: TempDirBuilder, tmpdir: Option<PathBuf>) -> LazyTempDir {
+        LazyTempDir {
+            builder,
+            tmpdir,
+            dir: OnceCell::new(),
+        }
+    }
+
+    /// Creates the directory if that hasn't happened yet, and returns it.
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`TempDirBuilder::tempdir_in`] are returned. A
+    /// failed attempt is not cached, so the next call tries again.
+    ///
+    /// [`TempDirBuilder::tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    pub fn ensure(&self) -> io::Result<&TempDir> {
+        if let Some(dir) = self.dir.get() {
+            return Ok(dir);
+        }
+        let dir = match self.tmpdir {
+            Some(ref tmpdir) => self.builder.tempdir_in(tmpdir)?,
+            None => self.builder.tempdir()?,
+        };
+        Ok(self.dir.get_or_init(|| dir))
+    }
+
+    /// Creates the directory if that hasn't happened yet, and returns its
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`ensure`] are returned.
+    ///
+    /// [`ensure`]: struct.LazyTempDir.html#method.ensure
+    pub fn path(&self) -> io::Result<&Path> {
+        self.ensure().map(TempDir::path)
+    }
+
+    /// Returns the directory if it has been created, without creating it.
+    pub fn get(&self) -> Option<&TempDir> {
+        self.dir.get()
+    }
+
+    /// Returns the directory if it has been created, handing over
+    /// responsibility for removing it.
+    pub fn into_inner(self) -> Option<TempDir> {
+        self.dir.into_inner()
+    }
+}
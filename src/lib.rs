@@ -10,7 +10,7 @@
 
 #![doc(html_logo_url = "https://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
        html_favicon_url = "https://www.rust-lang.org/favicon.ico",
-       html_root_url = "https://docs.rs/tempdir/0.3.7")]
+       html_root_url = "https://docs.rs/tempdir/0.4.0")]
 #![cfg_attr(test, deny(warnings))]
 
 //! Temporary directories of files.
@@ -20,7 +20,11 @@
 //! `TempDir` creates a new directory with a randomly generated name
 //! and a prefix of your choosing.
 //!
+//! For a single file, the [`TempFile`] type follows the same naming
+//! scheme and deletes the file once it goes out of scope.
+//!
 //! [`TempDir`]: struct.TempDir.html
+//! [`TempFile`]: struct.TempFile.html
 //! [`std::env::temp_dir()`]: https://doc.rust-lang.org/std/env/fn.temp_dir.html
 //!
 //! # Examples
@@ -56,16 +60,86 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Cargo features
+//!
+//! - `rand` (enabled by default) draws random names from the `rand`
+//!   crate and allows supplying a custom generator.
+//! - `minimal-rng` reads random names straight from the operating
+//!   system through `getrandom`, for builds without `rand`. Enable it
+//!   with `default-features = false`. Names use the same alphabet and
+//!   length either way.
+//! - `serde` implements `Serialize` and `Deserialize` for `TempDir`.
+//! - `tokio` adds `TempDir::close_async`.
+//! - `windows-acl` lets `TempDirBuilder::restrict_to_user` limit access
+//!   to the current user on Windows.
+//!
+//! # Debugging
+//!
+//! Setting the `TEMPDIR_KEEP` environment variable to `1` keeps every
+//! `TempDir` on drop and prints where it is, so the directories left by
+//! a failing run can be inspected. See [`KEEP_ENV_VAR`].
+//!
+//! [`KEEP_ENV_VAR`]: constant.KEEP_ENV_VAR.html
 
+#[cfg(not(any(feature = "rand", feature = "minimal-rng")))]
+compile_error!("either the `rand` or the `minimal-rng` feature must be enabled");
+
+#[cfg(all(feature = "minimal-rng", not(feature = "rand")))]
+extern crate getrandom;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "rand")]
 extern crate rand;
 extern crate remove_dir_all;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(windows)]
+extern crate winapi;
+
+#[cfg(feature = "tokio")]
+pub use close_async::CloseAsync;
+pub use cwd::CwdGuard;
+pub use error::TempDirError;
+pub use file::TempFile;
+pub use lazy::LazyTempDir;
+pub use provider::{FsProvider, RealFs};
+pub use set::TempDirSet;
 
+#[cfg(feature = "tokio")]
+mod close_async;
+#[cfg(all(windows, feature = "windows-acl"))]
+mod acl;
+mod cwd;
+mod digest;
+mod error;
+mod file;
+mod lazy;
+mod provider;
+mod random;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod set;
+mod walk;
+
+use std::borrow::Cow;
+use std::cmp;
+use std::convert::TryFrom;
 use std::env;
 use std::io::{self, Error, ErrorKind};
 use std::fmt;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::ops;
 use std::path::{self, PathBuf, Path};
-use rand::{thread_rng, Rng};
+use std::process;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "rand")]
+use rand::Rng;
 use remove_dir_all::remove_dir_all;
 
 /// A directory in the filesystem that is automatically deleted when
@@ -83,12 +157,17 @@ use remove_dir_all::remove_dir_all;
 ///
 /// After creating a `TempDir`, work with the file system by doing
 /// standard [`std::fs`] file system operations on its [`Path`],
-/// which can be retrieved with [`TempDir::path`]. Once the `TempDir`
-/// value is dropped, the directory at the path will be deleted, along
-/// with any files and directories it contains. It is your responsibility
+/// which can be retrieved with [`TempDir::path`] or reached directly
+/// through `TempDir`'s `Deref<Target = Path>` implementation, as in
+/// `tmp_dir.join("file.txt")`. Once the `TempDir` value is dropped,
+/// the directory at the path will be deleted, along with any files
+/// and directories it contains. It is your responsibility
 /// to ensure that no further file system operations are attempted
 /// inside the temporary directory once it has been deleted.
 ///
+/// Symbolic links inside the directory are deleted themselves; the
+/// files and directories they point to are left untouched.
+///
 /// Various platform-specific conditions may cause `TempDir` to fail
 /// to delete the underlying directory. It's important to ensure that
 /// handles (like [`File`] and [`ReadDir`]) to files inside the
@@ -96,6 +175,11 @@ use remove_dir_all::remove_dir_all;
 /// `TempDir` destructor will silently ignore any errors in deleting
 /// the directory; to instead handle errors call [`TempDir::close`].
 ///
+/// With the `serde` feature enabled, `TempDir` implements `Serialize`
+/// as its path, and `Deserialize` by adopting the deserialized path
+/// with [`TempDir::from_existing_path`]. A deserialized `TempDir`
+/// therefore takes over deleting the directory when it is dropped.
+///
 /// Note that if the program exits before the `TempDir` destructor is
 /// run, such as via [`std::process::exit`], by segfaulting, or by
 /// receiving a signal like `SIGINT`, then the temporary directory
@@ -105,6 +189,7 @@ use remove_dir_all::remove_dir_all;
 /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
 /// [`ReadDir`]: http://doc.rust-lang.org/std/fs/struct.ReadDir.html
 /// [`TempDir::close`]: struct.TempDir.html#method.close
+/// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
 /// [`TempDir::new`]: struct.TempDir.html#method.new
 /// [`TempDir::new_in`]: struct.TempDir.html#method.new_in
 /// [`TempDir::path`]: struct.TempDir.html#method.path
@@ -114,16 +199,80 @@ use remove_dir_all::remove_dir_all;
 /// [`std::process::exit`]: http://doc.rust-lang.org/std/process/fn.exit.html
 pub struct TempDir {
     path: Option<PathBuf>,
+    prefix: String,
+    keep: bool,
+    keep_on_panic: bool,
+    on_cleanup_error: Option<CleanupErrorHandler>,
+    panic_on_cleanup_error: bool,
+    // The directory lives inside another `TempDir`, which may have removed
+    // it already by the time this one is dropped.
+    nested: bool,
+    attempts: u32,
+    // False for directories adopted with `from_existing_path`.
+    created_by_us: bool,
+    // `None` means the real file system.
+    fs: Option<Arc<dyn FsProvider>>,
+    // How deep the recursive helpers may go below the directory.
+    max_depth: usize,
+    cleanup_mode: CleanupMode,
 }
 
+type CleanupErrorHandler = Arc<dyn Fn(&Path, &io::Error) + Send + Sync>;
+
 // How many times should we (re)try finding an unused random name? It should be
 // enough that an attacker will run out of luck before we run out of patience.
 const NUM_RETRIES: u32 = 1 << 31;
-// How many characters should we include in a random file name? It needs to
-// be enough to dissuade an attacker from trying to preemptively create names
-// of that length, but not so huge that we unnecessarily drain the random number
-// generator of entropy.
-const NUM_RAND_CHARS: usize = 12;
+/// The number of random characters in a directory name unless
+/// [`TempDirBuilder::rand_bytes`] says otherwise.
+///
+/// Code that checks or budgets for the length of generated names can use
+/// this instead of hard-coding the value. A name made with the default
+/// settings is the prefix, a `.` separator and this many characters.
+///
+/// [`TempDirBuilder::rand_bytes`]: struct.TempDirBuilder.html#method.rand_bytes
+///
+/// # Examples
+///
+/// ```
+/// use tempdir::{TempDir, DEFAULT_RAND_CHARS};
+///
+/// # use std::io;
+/// # fn run() -> Result<(), io::Error> {
+/// let tmp_dir = TempDir::new("example")?;
+/// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+/// assert_eq!(name.len(), "example.".len() + DEFAULT_RAND_CHARS);
+/// # Ok(())
+/// # }
+/// ```
+// It needs to be enough to dissuade an attacker from trying to preemptively
+// create names of that length, but not so huge that we unnecessarily drain the
+// random number generator of entropy.
+pub const DEFAULT_RAND_CHARS: usize = 12;
+// The longest file name most file systems accept, in bytes.
+const MAX_NAME_LEN: usize = 255;
+// How many directories deep the recursive helpers go by default. Paths
+// that deep are already longer than most systems allow.
+const MAX_DEPTH: usize = 1024;
+
+// Set by `TempDir::set_default_base`.
+static DEFAULT_BASE: OnceLock<PathBuf> = OnceLock::new();
+
+/// The environment variable that stops every [`TempDir`] from removing
+/// its directory on drop.
+///
+/// When `TEMPDIR_KEEP` is set to anything other than an empty string or
+/// `0`, dropping a `TempDir` leaves the directory in place and prints its
+/// path to stderr, so the contents can be inspected after a failing test
+/// or CI run without changing any code. The variable is read the first
+/// time a `TempDir` is dropped and the answer is kept for the rest of the
+/// process. Explicit calls such as [`close`] still remove the directory.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`close`]: struct.TempDir.html#method.close
+pub const KEEP_ENV_VAR: &str = "TEMPDIR_KEEP";
+
+// Whether `KEEP_ENV_VAR` was set, read on first use.
+static KEEP_FROM_ENV: OnceLock<bool> = OnceLock::new();
 
 impl TempDir {
     /// Attempts to make a temporary directory inside of `env::temp_dir()` whose
@@ -131,6 +280,12 @@ impl TempDir {
     /// everything inside it will be automatically deleted once the
     /// returned `TempDir` is destroyed.
     ///
+    /// If a default base directory has been set with
+    /// [`TempDir::set_default_base`], it is used instead of
+    /// `env::temp_dir()`.
+    ///
+    /// [`TempDir::set_default_base`]: struct.TempDir.html#method.set_default_base
+    ///
     /// # Errors
     ///
     /// If the directory can not be created, `Err` is returned.
@@ -157,7 +312,97 @@ impl TempDir {
     /// # }
     /// ```
     pub fn new(prefix: &str) -> io::Result<TempDir> {
-        TempDir::new_in(&env::temp_dir(), prefix)
+        TempDir::builder().prefix(prefix).tempdir()
+    }
+
+    /// Sets the directory that [`TempDir::new`] and the other
+    /// constructors without an explicit location create directories in,
+    /// in place of `env::temp_dir()`, for the rest of the process.
+    ///
+    /// This lets a program or test suite choose the location of all its
+    /// temporary directories once at startup, for example to put them on
+    /// a fast tmpfs in CI. It affects [`TempDir::new`],
+    /// [`TempDirBuilder::tempdir`], [`TempDirBuilder::tempdir_in_namespace`]
+    /// and [`TempFile::new`]. Functions that take a location, and
+    /// [`TempDir::new_in_tmpdir_env`], are unaffected.
+    ///
+    /// The base can be set only once, and it is safe to call from any
+    /// thread; constructors racing with the call use either the old or
+    /// the new location. A relative `path` is resolved against the
+    /// current directory at the time of the call. The directory is not
+    /// created.
+    ///
+    /// [`TempDir::new`]: struct.TempDir.html#method.new
+    /// [`TempDirBuilder::tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`TempDirBuilder::tempdir_in_namespace`]: struct.TempDirBuilder.html#method.tempdir_in_namespace
+    /// [`TempFile::new`]: struct.TempFile.html#method.new
+    /// [`TempDir::new_in_tmpdir_env`]: struct.TempDir.html#method.new_in_tmpdir_env
+    ///
+    /// # Errors
+    ///
+    /// If the default base has already been set, an error with
+    /// `ErrorKind::AlreadyExists` is returned and the base is unchanged.
+    /// If `path` is relative and the current directory can not be
+    /// determined, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// TempDir::set_default_base("/dev/shm/my-tests")?;
+    /// let tmp_dir = TempDir::new("example")?;
+    /// assert!(tmp_dir.path().starts_with("/dev/shm/my-tests"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_default_base<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            env::current_dir()?.join(path)
+        };
+        DEFAULT_BASE.set(path).map_err(|_| {
+            Error::new(ErrorKind::AlreadyExists, "the default base directory is already set")
+        })
+    }
+
+    /// Attempts to make a temporary directory inside of the directory
+    /// named by the environment, whose name will have the prefix
+    /// `prefix`. The directory and everything inside it will be
+    /// automatically deleted once the returned `TempDir` is destroyed.
+    ///
+    /// Unlike [`TempDir::new`], which relies on the platform rules of
+    /// `env::temp_dir()`, the base directory is resolved in a fixed
+    /// order: on Unix, the `TMPDIR` variable is used; on Windows, `TEMP`
+    /// and then `TMP`. Variables that are unset or empty are skipped,
+    /// and if none is set, `env::temp_dir()` is used.
+    ///
+    /// [`TempDir::new`]: struct.TempDir.html#method.new
+    ///
+    /// # Errors
+    ///
+    /// If the variable names a directory that does not exist, an error
+    /// with `ErrorKind::NotFound` naming the variable is returned. If the
+    /// directory can not be created, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new_in_tmpdir_env("example")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_in_tmpdir_env(prefix: &str) -> io::Result<TempDir> {
+        let tmpdir = tmpdir_from_env()?;
+        TempDir::new_in(tmpdir, prefix)
     }
 
     /// Attempts to make a temporary directory inside of `tmpdir`
@@ -167,7 +412,9 @@ impl TempDir {
     ///
     /// # Errors
     ///
-    /// If the directory can not be created, `Err` is returned.
+    /// If `prefix` contains a path separator or a NUL byte, an error
+    /// with `ErrorKind::InvalidInput` is returned. If the directory can
+    /// not be created, `Err` is returned.
     ///
     /// # Examples
     ///
@@ -188,36 +435,231 @@ impl TempDir {
     /// # }
     /// ```
     pub fn new_in<P: AsRef<Path>>(tmpdir: P, prefix: &str) -> io::Result<TempDir> {
+        TempDir::builder().prefix(prefix).tempdir_in(tmpdir)
+    }
+
+    /// Attempts to make `count` temporary directories inside of `tmpdir`,
+    /// each named with the prefix `prefix` and its own random characters.
+    ///
+    /// Either all of the directories are created or none are: if one
+    /// can't be created, those made before it are removed again before
+    /// the error is returned. Each returned `TempDir` is cleaned up on
+    /// drop as usual, so dropping the `Vec` removes the whole batch. See
+    /// [`TempDirBuilder::tempdir_batch_in`] for other settings.
+    ///
+    /// [`TempDirBuilder::tempdir_batch_in`]: struct.TempDirBuilder.html#method.tempdir_batch_in
+    ///
+    /// # Errors
+    ///
+    /// If `prefix` contains a path separator or a NUL byte, an error
+    /// with `ErrorKind::InvalidInput` is returned. If any directory can
+    /// not be created, the error for that directory is returned; errors
+    /// removing the ones already created are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// // One directory for each shard of the test data.
+    /// let shards = TempDir::new_batch_in(".", "shard", 4)?;
+    /// assert_eq!(shards.len(), 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_batch_in<P: AsRef<Path>>(tmpdir: P, prefix: &str, count: usize)
+                                        -> io::Result<Vec<TempDir>> {
+        TempDir::builder().prefix(prefix).tempdir_batch_in(tmpdir, count)
+    }
+
+    /// Attempts to make a temporary directory inside of `env::temp_dir()`
+    /// like [`TempDir::new`], returning a [`TempDirError`] on failure.
+    ///
+    /// [`TempDir::new`]: struct.TempDir.html#method.new
+    /// [`TempDirError`]: enum.TempDirError.html
+    ///
+    /// # Errors
+    ///
+    /// If every name tried already exists, `TempDirError::Exhausted` is
+    /// returned. If `prefix` contains a path separator or a NUL byte,
+    /// `TempDirError::InvalidName` is returned. Other failures are
+    /// returned as `TempDirError::Io`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::{TempDir, TempDirError};
+    ///
+    /// match TempDir::try_new("example") {
+    ///     Ok(tmp_dir) => println!("created {}", tmp_dir.path().display()),
+    ///     Err(TempDirError::Exhausted { attempts }) => {
+    ///         eprintln!("no free name after {} attempts", attempts);
+    ///     }
+    ///     Err(e) => eprintln!("failed to create a temporary directory: {}", e),
+    /// }
+    /// ```
+    pub fn try_new(prefix: &str) -> Result<TempDir, TempDirError> {
+        TempDir::builder().prefix(prefix).try_tempdir()
+    }
+
+    /// Attempts to make a temporary directory inside of `tmpdir` like
+    /// [`TempDir::new_in`], returning a [`TempDirError`] on failure.
+    ///
+    /// [`TempDir::new_in`]: struct.TempDir.html#method.new_in
+    /// [`TempDirError`]: enum.TempDirError.html
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`TempDir::try_new`] are returned.
+    ///
+    /// [`TempDir::try_new`]: struct.TempDir.html#method.try_new
+    pub fn try_new_in<P: AsRef<Path>>(tmpdir: P, prefix: &str) -> Result<TempDir, TempDirError> {
+        TempDir::builder().prefix(prefix).try_tempdir_in(tmpdir)
+    }
+
+    /// Attempts to make a directory named exactly `name` inside of
+    /// `tmpdir`. The directory and everything inside it will be
+    /// automatically deleted once the returned `TempDir` is destroyed.
+    ///
+    /// Unlike the other constructors, no random characters are added to
+    /// the name, so the directory's location is predictable. Prefer
+    /// [`TempDir::new_in`] for directories in shared locations, where
+    /// another user could create the name first.
+    ///
+    /// [`TempDir::new_in`]: struct.TempDir.html#method.new_in
+    ///
+    /// # Errors
+    ///
+    /// If `name` is not a single, normal path component, an error with
+    /// `ErrorKind::InvalidInput` is returned. If `tmpdir/name` already
+    /// exists, an error with `ErrorKind::AlreadyExists` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let cache = TempDir::new_named_in(".", "cache")?;
+    /// assert!(cache.path().ends_with("cache"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_named_in<P: AsRef<Path>>(tmpdir: P, name: &str) -> io::Result<TempDir> {
+        if name.is_empty() || name == "." || name == ".." || has_separator(name) ||
+           name.contains('\0') {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "name must be a single, non-empty path component"));
+        }
+
         let storage;
         let mut tmpdir = tmpdir.as_ref();
         if !tmpdir.is_absolute() {
             let cur_dir = env::current_dir()?;
             storage = cur_dir.join(tmpdir);
             tmpdir = &storage;
-            // return TempDir::new_in(&cur_dir.join(tmpdir), prefix);
         }
 
-        let mut rng = thread_rng();
-        for _ in 0..NUM_RETRIES {
-            let suffix: String = rng.gen_ascii_chars().take(NUM_RAND_CHARS).collect();
-            let leaf = if !prefix.is_empty() {
-                format!("{}.{}", prefix, suffix)
-            } else {
-                // If we're given an empty string for a prefix, then creating a
-                // directory starting with "." would lead to it being
-                // semi-invisible on some systems.
-                suffix
-            };
-            let path = tmpdir.join(&leaf);
-            match fs::create_dir(&path) {
-                Ok(_) => return Ok(TempDir { path: Some(path) }),
-                Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
-                Err(e) => return Err(e),
-            }
+        let path = tmpdir.join(name);
+        create_dir(&path, 0o700)?;
+        Ok(TempDir::wrap(path))
+    }
+
+    /// Returns a [`TempDirBuilder`] for configuring how a temporary
+    /// directory is named and where it is created.
+    ///
+    /// [`TempDirBuilder`]: struct.TempDirBuilder.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::builder()
+    ///     .prefix("example")
+    ///     .suffix(".tmp")
+    ///     .tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> TempDirBuilder {
+        TempDirBuilder::new()
+    }
+
+    /// Takes ownership of an existing directory at `path`, which will
+    /// be deleted along with everything inside it once the returned
+    /// `TempDir` is destroyed.
+    ///
+    /// This is the inverse of [`into_path`], and can be used to resume
+    /// cleanup of a directory that was previously released from a
+    /// `TempDir`. A relative `path` is resolved against the current
+    /// directory.
+    ///
+    /// [`into_path`]: struct.TempDir.html#method.into_path
+    ///
+    /// # Errors
+    ///
+    /// If `path` does not exist, an error with `ErrorKind::NotFound` is
+    /// returned. If it exists but is not a directory, an error with
+    /// `ErrorKind::NotADirectory` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_path = TempDir::new("example")?.into_path();
+    ///
+    /// // Work with the directory without it being deleted...
+    ///
+    /// // ...and then hand it back to a `TempDir` to have it cleaned up.
+    /// let tmp_dir = TempDir::from_existing_path(tmp_path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_existing_path(path: PathBuf) -> io::Result<TempDir> {
+        let path = if path.is_absolute() {
+            path
+        } else {
+            env::current_dir()?.join(path)
+        };
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(Error::new(ErrorKind::NotADirectory,
+                                  format!("{} is not a directory", path.display())));
+        }
+        let mut dir = TempDir::wrap(path);
+        dir.attempts = 0;
+        dir.created_by_us = false;
+        Ok(dir)
+    }
+
+    fn wrap(path: PathBuf) -> TempDir {
+        TempDir {
+            path: Some(path),
+            prefix: String::new(),
+            keep: false,
+            keep_on_panic: false,
+            on_cleanup_error: None,
+            panic_on_cleanup_error: false,
+            nested: false,
+            attempts: 1,
+            created_by_us: true,
+            fs: None,
+            max_depth: MAX_DEPTH,
+            cleanup_mode: CleanupMode::Recursive,
         }
+    }
 
-        Err(Error::new(ErrorKind::AlreadyExists,
-                       "too many temporary directories already exist"))
+    // Removes `path` through the provider the directory was created with.
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        remove_with(self.fs.as_deref(), self.cleanup_mode, path)
     }
 
     /// Accesses the [`Path`] to the temporary directory.
@@ -252,11 +694,21 @@ impl TempDir {
         self.path.as_ref().unwrap()
     }
 
-    /// Unwraps the [`Path`] contained in the `TempDir` and
-    /// returns it. This destroys the `TempDir` without deleting the
-    /// directory represented by the returned `Path`.
+    /// Returns the canonical, absolute form of the path to the temporary
+    /// directory, with all symbolic links resolved.
     ///
-    /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
+    /// [`path`] returns the path as it was created, which may go through
+    /// symbolic links: on macOS, for example, `env::temp_dir()` is under
+    /// `/var`, which links to `/private/var`. Comparing against paths
+    /// that were canonicalized elsewhere needs this form instead. It is
+    /// computed with `fs::canonicalize` on every call.
+    ///
+    /// [`path`]: struct.TempDir.html#method.path
+    ///
+    /// # Errors
+    ///
+    /// The same errors as `fs::canonicalize` are returned, for instance
+    /// if the directory has been removed.
     ///
     /// # Examples
     ///
@@ -267,90 +719,4108 @@ impl TempDir {
     /// # use std::io;
     /// # fn run() -> Result<(), io::Error> {
     /// let tmp_dir = TempDir::new("example")?;
+    /// let file_path = tmp_dir.path().join("output.txt");
+    /// fs::write(&file_path, b"data")?;
+    /// assert!(fs::canonicalize(&file_path)?.starts_with(tmp_dir.canonical_path()?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn canonical_path(&self) -> io::Result<PathBuf> {
+        fs::canonicalize(self.path())
+    }
+
+    /// Returns the path to the temporary directory as a `&str`, or `None`
+    /// if it is not valid UTF-8.
     ///
-    /// // Convert `tmp_dir` into a `Path`, destroying the `TempDir`
-    /// // without deleting the directory.
-    /// let tmp_path = tmp_dir.into_path();
+    /// This is convenient for APIs that only accept string paths, such as
+    /// building shell commands. Paths on Unix may hold arbitrary bytes and
+    /// paths on Windows unpaired surrogates, so callers must handle
+    /// `None`; a base directory with such a name is rare but possible.
     ///
-    /// // Delete the temporary directory ourselves.
-    /// fs::remove_dir_all(tmp_path)?;
+    /// # Examples
+    ///
+    /// ```
+    /// use std::process::Command;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// if let Some(dir) = tmp_dir.path_str() {
+    ///     Command::new("sh").arg("-c").arg(format!("touch '{}/done'", dir)).status()?;
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn into_path(mut self) -> PathBuf {
-        self.path.take().unwrap()
+    pub fn path_str(&self) -> Option<&str> {
+        self.path().to_str()
     }
 
-    /// Closes and removes the temporary directory, returing a `Result`.
+    /// Returns the prefix the directory was named with.
     ///
-    /// Although `TempDir` removes the directory on drop, in the destructor
-    /// any errors are ignored. To detect errors cleaning up the temporary
-    /// directory, call `close` instead.
+    /// This is empty for directories created without a prefix, and for
+    /// directories adopted with [`TempDir::from_existing_path`] or
+    /// created with [`TempDir::new_named_in`].
     ///
-    /// # Errors
+    /// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+    /// [`TempDir::new_named_in`]: struct.TempDir.html#method.new_named_in
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns how many names were tried before the directory was
+    /// created, including the one that succeeded.
     ///
-    /// This function may return a variety of [`std::io::Error`]s that result from deleting
-    /// the files and directories contained with the temporary directory,
-    /// as well as from deleting the temporary directory itself. These errors
-    /// may be platform specific.
+    /// This is almost always `1` for randomly named directories. Higher
+    /// counts mean names collided with existing entries, and a rising
+    /// rate of collisions in a shared temporary directory can point to
+    /// too few random characters or to someone claiming names ahead of
+    /// time. Directories adopted with [`TempDir::from_existing_path`]
+    /// report `0`.
     ///
-    /// [`std::io::Error`]: http://doc.rust-lang.org/std/io/struct.Error.html
+    /// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
     ///
     /// # Examples
     ///
     /// ```
-    /// use std::fs::File;
-    /// use std::io::Write;
     /// use tempdir::TempDir;
     ///
     /// # use std::io;
     /// # fn run() -> Result<(), io::Error> {
-    /// // Create a directory inside of `std::env::temp_dir()`, named with
-    /// // the prefix, "example".
     /// let tmp_dir = TempDir::new("example")?;
-    /// let file_path = tmp_dir.path().join("my-temporary-note.txt");
-    /// let mut tmp_file = File::create(file_path)?;
-    /// writeln!(tmp_file, "Brian was here. Briefly.")?;
-    ///
-    /// // By closing the `TempDir` explicitly we can check that it has
-    /// // been deleted successfully. If we don't close it explicitly,
-    /// // the directory will still be deleted when `tmp_dir` goes out
-    /// // of scope, but we won't know whether deleting the directory
-    /// // succeeded.
-    /// drop(tmp_file);
-    /// tmp_dir.close()?;
+    /// if tmp_dir.attempts() > 1 {
+    ///     eprintln!("{} name collisions", tmp_dir.attempts() - 1);
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn close(mut self) -> io::Result<()> {
-        let result = remove_dir_all(self.path());
-
-        // Prevent the Drop impl from removing the dir a second time.
-        self.path = None;
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
 
-        result
+    /// Returns the directory the temporary directory was created in.
+    ///
+    /// This is the parent of [`path`], and can be used to create
+    /// related temporary directories alongside this one.
+    ///
+    /// [`path`]: struct.TempDir.html#method.path
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let sibling = TempDir::new_in(tmp_dir.base(), tmp_dir.prefix())?;
+    /// assert_eq!(sibling.base(), tmp_dir.base());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn base(&self) -> &Path {
+        // Every path we manage is absolute and names a directory inside of
+        // another one, so it always has a parent.
+        self.path().parent().unwrap()
     }
-}
 
-impl AsRef<Path> for TempDir {
-    fn as_ref(&self) -> &Path {
-        self.path()
+    /// Returns the path of `path` joined onto the temporary directory.
+    ///
+    /// This only computes the path; nothing is created on disk. `path`
+    /// is not checked, so an absolute path or one with `..` components
+    /// can point outside the temporary directory.
+    ///
+    /// This method is deprecated because that unchecked join makes it
+    /// easy to escape the directory by accident. It is kept, infallible,
+    /// for compatibility; use [`try_child`] instead, which returns an
+    /// error for paths that would leave the temporary directory.
+    ///
+    /// [`try_child`]: struct.TempDir.html#method.try_child
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(deprecated)]
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let file_path = tmp_dir.path().join("my-temporary-note.txt");
+    /// assert_eq!(file_path, tmp_dir.try_child("my-temporary-note.txt")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[deprecated(note = "use `try_child`, which rejects paths outside the directory")]
+    pub fn child<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path().join(path)
     }
-}
+
+    /// Returns the path of the relative path `rel` joined onto the
+    /// temporary directory, checking that it stays inside.
+    ///
+    /// Unlike the deprecated [`child`], this resolves `.` and `..` components and fails
+    /// if the result would lie outside the temporary directory, which
+    /// makes it suitable for names from untrusted input. The check is
+    /// purely lexical: symbolic links inside the directory are not
+    /// resolved. Nothing is created on disk.
+    ///
+    /// [`child`]: struct.TempDir.html#method.child
+    ///
+    /// # Errors
+    ///
+    /// If `rel` is absolute or climbs out of the temporary directory with
+    /// `..`, an error with `ErrorKind::InvalidInput` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let path = tmp_dir.try_child("logs/../report.txt")?;
+    /// assert_eq!(path, tmp_dir.path().join("report.txt"));
+    /// assert!(tmp_dir.try_child("../escape").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_child<P: AsRef<Path>>(&self, rel: P) -> io::Result<PathBuf> {
+        self.contained_path(rel.as_ref())
+    }
+
+    // Joins `rel` onto the directory, resolving `.` and `..`, and rejects
+    // results that would not be inside it.
+    fn contained_path(&self, rel: &Path) -> io::Result<PathBuf> {
+        let escapes = || {
+            Error::new(ErrorKind::InvalidInput,
+                       format!("{} is not a path inside the temporary directory",
+                               rel.display()))
+        };
+
+        let mut path = self.path().to_path_buf();
+        let mut depth = 0;
+        for component in rel.components() {
+            match component {
+                path::Component::Normal(part) => {
+                    path.push(part);
+                    depth += 1;
+                }
+                path::Component::CurDir => {}
+                path::Component::ParentDir if depth > 0 => {
+                    path.pop();
+                    depth -= 1;
+                }
+                _ => return Err(escapes()),
+            }
+        }
+        Ok(path)
+    }
+
+    /// Returns whether `path` names the temporary directory or something
+    /// inside it.
+    ///
+    /// This is `path.starts_with(self.path())` after resolving `.` and
+    /// `..` components in both paths, so `dir/sub/../file` is inside and
+    /// `dir/../elsewhere` is not, and trailing separators make no
+    /// difference. Only the paths are compared: nothing is read from the
+    /// file system, so symbolic links are not resolved and `path` need
+    /// not exist. A relative `path` is never inside, since the temporary
+    /// directory's path is absolute.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// assert!(tmp_dir.contains(tmp_dir.path().join("out/report.txt")));
+    /// assert!(!tmp_dir.contains(tmp_dir.path().join("../elsewhere")));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        normalize_lexically(path.as_ref()).starts_with(normalize_lexically(self.path()))
+    }
+
+    /// Creates an empty file at the relative path `rel` inside the
+    /// temporary directory, returning its full path.
+    ///
+    /// `rel` is resolved as by [`try_child`]. Missing parent directories
+    /// are created first. An existing file at that path is truncated, as
+    /// with `File::create`.
+    ///
+    /// [`try_child`]: struct.TempDir.html#method.try_child
+    ///
+    /// # Errors
+    ///
+    /// If `rel` is absolute or climbs out of the temporary directory with
+    /// `..`, an error with `ErrorKind::InvalidInput` is returned. Errors
+    /// creating the parent directories or the file are returned as they
+    /// occur.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let marker = tmp_dir.create_file("state/ready")?;
+    /// assert!(marker.is_file());
+    /// assert_eq!(marker, tmp_dir.path().join("state/ready"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_file<P: AsRef<Path>>(&self, rel: P) -> io::Result<PathBuf> {
+        let path = self.contained_path(rel.as_ref())?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::File::create(&path)?;
+        Ok(path)
+    }
+
+    /// Creates a new file named `name` directly inside the temporary
+    /// directory and opens it for writing, returning its path and the
+    /// open file.
+    ///
+    /// The file is opened with `create_new`, so an existing file is never
+    /// clobbered, not even one created by another process between the
+    /// caller choosing the name and this call.
+    ///
+    /// # Errors
+    ///
+    /// If `name` is empty, is `.` or `..`, or contains a path separator
+    /// or a NUL byte, an error with `ErrorKind::InvalidInput` is returned.
+    /// If something named `name` already exists, an error with
+    /// `ErrorKind::AlreadyExists` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let (path, mut file) = tmp_dir.new_file("input.txt")?;
+    /// writeln!(file, "Brian was here. Briefly.")?;
+    /// assert_eq!(path, tmp_dir.path().join("input.txt"));
+    /// assert!(tmp_dir.new_file("input.txt").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_file(&self, name: &str) -> io::Result<(PathBuf, fs::File)> {
+        let path = self.contained_path(Path::new(name))?;
+        // On top of staying inside, the name must be a single normal
+        // component, which rules out separators, `.` and the empty name.
+        // A NUL byte is rejected by `open`.
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(path::Component::Normal(_)), None) => {}
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      format!("`{}` is not a valid file name", name)))
+            }
+        }
+        let file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+        Ok((path, file))
+    }
+
+    /// Fills the temporary directory with `dirs` subdirectories, each
+    /// holding `files_per_dir` empty files, as a standard fixture for file
+    /// system benchmarks.
+    ///
+    /// The subdirectories are named `dir0`, `dir1` and so on, and the
+    /// files in each of them `file0`, `file1` and so on. Creation stops
+    /// at the first error, leaving whatever was created so far in place
+    /// to be removed with the rest of the directory.
+    ///
+    /// # Errors
+    ///
+    /// The first error creating a subdirectory or file is returned. If
+    /// an entry with one of the names already exists, an error with
+    /// `ErrorKind::AlreadyExists` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.reserve_layout(4, 100)?;
+    /// assert_eq!(tmp_dir.list_files()?.len(), 400);
+    /// assert!(tmp_dir.path().join("dir3/file99").is_file());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reserve_layout(&self, dirs: usize, files_per_dir: usize) -> io::Result<()> {
+        for d in 0..dirs {
+            let dir = self.path().join(format!("dir{}", d));
+            fs::create_dir(&dir)?;
+            for f in 0..files_per_dir {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(dir.join(format!("file{}", f)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a symbolic link at the relative path `link_rel` inside the
+    /// temporary directory, pointing to `original`, and returns the full
+    /// path of the link.
+    ///
+    /// `link_rel` is resolved as by [`try_child`] and missing parent
+    /// directories are created first. `original` is stored in the link as
+    /// given: it may lie outside the temporary directory, need not exist,
+    /// and if relative is resolved from the directory holding the link.
+    ///
+    /// Windows distinguishes links to files from links to directories.
+    /// A directory link is made if `original` currently names a
+    /// directory, and a file link otherwise. Creating symbolic links on
+    /// Windows requires the `SeCreateSymbolicLinkPrivilege` privilege,
+    /// which by default only administrators have unless Developer Mode is
+    /// enabled; without it this fails with `ErrorKind::PermissionDenied`.
+    ///
+    /// [`try_child`]: struct.TempDir.html#method.try_child
+    ///
+    /// # Errors
+    ///
+    /// If `link_rel` is absolute or climbs out of the temporary directory
+    /// with `..`, an error with `ErrorKind::InvalidInput` is returned. If
+    /// something already exists at the link path, an error with
+    /// `ErrorKind::AlreadyExists` is returned. On platforms without
+    /// symbolic links an error with `ErrorKind::Unsupported` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.create_file("target.txt")?;
+    /// let link = tmp_dir.symlink("../target.txt", "links/to-target")?;
+    /// assert_eq!(fs::read_link(&link)?, std::path::Path::new("../target.txt"));
+    /// assert!(link.is_file());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(&self, original: P, link_rel: Q)
+                                                   -> io::Result<PathBuf> {
+        let link = self.contained_path(link_rel.as_ref())?;
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        create_symlink(original.as_ref(), &link)?;
+        Ok(link)
+    }
+
+    /// Changes the current directory of the process to the temporary
+    /// directory, returning a guard that changes it back when dropped.
+    ///
+    /// The current directory is global to the process, so this affects
+    /// all threads, not just the calling one. It is not safe to use from
+    /// tests that run in parallel with other tests relying on the
+    /// current directory.
+    ///
+    /// # Errors
+    ///
+    /// If the current directory can not be read or changed, the
+    /// underlying error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// {
+    ///     let _guard = tmp_dir.enter()?;
+    ///     File::create("my-temporary-note.txt")?;
+    /// }
+    /// // The previous current directory has been restored here.
+    /// assert!(tmp_dir.path().join("my-temporary-note.txt").exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enter<'a>(&'a self) -> io::Result<CwdGuard<'a>> {
+        CwdGuard::enter(self)
+    }
+
+    /// Attempts to make a temporary directory inside of this one whose
+    /// name will have the prefix `prefix`, returning a separate `TempDir`
+    /// managing it.
+    ///
+    /// This is the same as `TempDir::new_in(self.path(), prefix)`. The
+    /// child is deleted either when it is dropped or, along with
+    /// everything else, when its parent is. A child that outlives its
+    /// parent finds its directory already gone when dropped, which is not
+    /// treated as an error.
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`TempDir::new_in`] are returned.
+    ///
+    /// [`TempDir::new_in`]: struct.TempDir.html#method.new_in
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let inputs = tmp_dir.new_child("inputs")?;
+    /// let outputs = tmp_dir.new_child("outputs")?;
+    /// assert_eq!(inputs.base(), tmp_dir.path());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_child(&self, prefix: &str) -> io::Result<TempDir> {
+        let mut child = TempDir::new_in(self.path(), prefix)?;
+        child.nested = true;
+        child.max_depth = self.max_depth;
+        Ok(child)
+    }
+
+    /// Returns an iterator over the entries directly inside the temporary
+    /// directory.
+    ///
+    /// This is the same as `fs::read_dir(self.path())`.
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`fs::read_dir`] are returned.
+    ///
+    /// [`fs::read_dir`]: http://doc.rust-lang.org/std/fs/fn.read_dir.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// File::create(tmp_dir.path().join("my-temporary-note.txt"))?;
+    /// for entry in tmp_dir.read_dir()? {
+    ///     println!("{}", entry?.path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_dir(&self) -> io::Result<fs::ReadDir> {
+        fs::read_dir(self.path())
+    }
+
+    /// Returns the metadata of the temporary directory itself, such as
+    /// its permissions and modification time.
+    ///
+    /// This is the same as `fs::metadata(self.path())`.
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`fs::metadata`] are returned, for example if
+    /// the directory has already been removed.
+    ///
+    /// [`fs::metadata`]: http://doc.rust-lang.org/std/fs/fn.metadata.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let metadata = tmp_dir.metadata()?;
+    /// assert!(metadata.is_dir());
+    /// println!("modified {:?}", metadata.modified()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        fs::metadata(self.path())
+    }
+
+    /// Returns the file system's identifier for the temporary directory:
+    /// its inode number on Unix, or its file index on Windows.
+    ///
+    /// The identifier stays the same for as long as the directory exists,
+    /// so recording it right after creation and comparing it again before
+    /// a sensitive operation detects the directory having been removed
+    /// and replaced, for example by a symbolic link, in the meantime. It
+    /// is only unique within one file system, and may be reused once the
+    /// directory is gone.
+    ///
+    /// # Errors
+    ///
+    /// If the directory can't be inspected, for example because it has
+    /// already been removed, `Err` is returned. On platforms other than
+    /// Unix and Windows an error with `ErrorKind::Unsupported` is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let id = tmp_dir.file_id()?;
+    ///
+    /// // ... later, before trusting the directory again:
+    /// if tmp_dir.file_id()? != id {
+    ///     panic!("{} was replaced", tmp_dir.path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn file_id(&self) -> io::Result<u64> {
+        file_id(self.path())
+    }
+
+    /// Returns whether the temporary directory contains no entries.
+    ///
+    /// Only the first entry is read, so this is cheap even for large
+    /// directories.
+    ///
+    /// # Errors
+    ///
+    /// If the directory can not be read, for example because it has
+    /// already been removed, the underlying error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// assert!(tmp_dir.is_empty()?);
+    ///
+    /// File::create(tmp_dir.path().join("my-temporary-note.txt"))?;
+    /// assert!(!tmp_dir.is_empty()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_empty(&self) -> io::Result<bool> {
+        match self.read_dir()?.next() {
+            None => Ok(true),
+            Some(Ok(_)) => Ok(false),
+            Some(Err(e)) => Err(e),
+        }
+    }
+
+    /// Returns the total size in bytes of all regular files inside the
+    /// temporary directory, recursively.
+    ///
+    /// Symbolic links are not followed, so files outside of the
+    /// directory are never counted and nothing is counted twice. The
+    /// sizes of directory entries themselves are not included.
+    ///
+    /// This visits every entry in the tree, which may be slow for large
+    /// or deep trees.
+    ///
+    /// # Errors
+    ///
+    /// If any directory or entry inside of it can not be read, the
+    /// underlying error is returned.
+    ///
+    /// If the tree is nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep, an error with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::Write;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let mut tmp_file = File::create(tmp_dir.path().join("my-temporary-note.txt"))?;
+    /// tmp_file.write_all(b"Brian was here. Briefly.")?;
+    /// assert_eq!(tmp_dir.size()?, 24);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn size(&self) -> io::Result<u64> {
+        let mut size = 0;
+        walk::walk(self.path(), self.max_depth, |_, metadata| {
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+            Ok(())
+        })?;
+        Ok(size)
+    }
+
+    /// Returns the number of entries inside the temporary directory,
+    /// recursively, counting files, directories and symbolic links alike.
+    ///
+    /// The directory itself is not counted, and symbolic links are not
+    /// followed. The tree is walked without recursion, down to at most
+    /// [`TempDirBuilder::max_depth`] directories below the directory.
+    ///
+    /// # Errors
+    ///
+    /// If any directory or entry inside of it can not be read, the
+    /// underlying error is returned.
+    ///
+    /// If the tree is nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep, an error with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::{self, File};
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// fs::create_dir(tmp_dir.path().join("notes"))?;
+    /// File::create(tmp_dir.path().join("notes/my-temporary-note.txt"))?;
+    /// assert_eq!(tmp_dir.entry_count()?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entry_count(&self) -> io::Result<usize> {
+        let mut count = 0;
+        walk::walk(self.path(), self.max_depth, |_, _| {
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    /// Returns the paths of all regular files inside the temporary
+    /// directory, recursively, relative to it and sorted.
+    ///
+    /// Only regular files are listed. Directories are left out, since
+    /// they are implied by the paths of the files inside them, and so
+    /// are symbolic links, which are not followed, whatever they point
+    /// to. Use [`entry_count`] or [`read_dir`] to look at those. Paths
+    /// are sorted component by component, so the list is the same from
+    /// run to run.
+    ///
+    /// [`entry_count`]: struct.TempDir.html#method.entry_count
+    /// [`read_dir`]: struct.TempDir.html#method.read_dir
+    ///
+    /// # Errors
+    ///
+    /// If any directory or entry inside of it can not be read, the
+    /// underlying error is returned.
+    ///
+    /// If the tree is nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep, an error with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.create_file("out/b.txt")?;
+    /// tmp_dir.create_file("a.txt")?;
+    /// assert_eq!(tmp_dir.list_files()?,
+    ///            vec![PathBuf::from("a.txt"), PathBuf::from("out/b.txt")]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_files(&self) -> io::Result<Vec<PathBuf>> {
+        let root = self.path();
+        let mut files = Vec::new();
+        walk::walk(root, self.max_depth, |path, metadata| {
+            if metadata.is_file() {
+                // Every walked path is below `root`.
+                files.push(path.strip_prefix(root).unwrap().to_path_buf());
+            }
+            Ok(())
+        })?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Flushes every regular file inside the temporary directory to disk,
+    /// then the directories themselves on Unix.
+    ///
+    /// Each file is opened and `sync_all` is called on it, so that data
+    /// written through other handles is on disk before the path is handed
+    /// to another process or the machine could crash. On Unix every
+    /// subdirectory and the temporary directory itself are synced
+    /// afterwards, which makes the entries for new files and
+    /// subdirectories durable. Other platforms
+    /// can't sync directories, so there only the files are flushed.
+    /// Symbolic links are not followed.
+    ///
+    /// This costs an open and a sync for every file, each of which may
+    /// wait for the disk, so it is best called once after all fixture
+    /// data has been written rather than after each file.
+    ///
+    /// # Errors
+    ///
+    /// The first error opening or syncing an entry is returned, and the
+    /// remaining entries are not synced. On Windows a read-only file
+    /// can't be opened for syncing and fails with
+    /// `ErrorKind::PermissionDenied`. If the directory is nested deeper
+    /// than [`TempDirBuilder::max_depth`] allows, an error with
+    /// `ErrorKind::InvalidData` is returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// fs::write(tmp_dir.path().join("input.txt"), "Brian was here. Briefly.")?;
+    /// tmp_dir.sync_all()?;
+    /// // Now hand `tmp_dir.path()` to a subprocess.
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sync_all(&self) -> io::Result<()> {
+        let root = self.path();
+        #[cfg(unix)]
+        let mut dirs = Vec::new();
+        walk::walk(root, self.max_depth, |path, metadata| {
+            if metadata.is_file() {
+                // Windows only flushes files opened for writing.
+                let file = if cfg!(windows) {
+                    fs::OpenOptions::new().write(true).open(path)?
+                } else {
+                    fs::File::open(path)?
+                };
+                file.sync_all()?;
+            }
+            #[cfg(unix)]
+            {
+                if metadata.is_dir() {
+                    dirs.push(path.to_path_buf());
+                }
+            }
+            Ok(())
+        })?;
+        #[cfg(unix)]
+        {
+            for dir in dirs {
+                fs::File::open(dir)?.sync_all()?;
+            }
+            fs::File::open(root)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Returns a digest of everything inside the temporary directory,
+    /// for telling whether its contents match those of an earlier run.
+    ///
+    /// Every entry below the directory contributes its path relative to
+    /// the directory, whether it is a file, directory or symbolic link,
+    /// and respectively the file's contents or the link's target path.
+    /// Links are not followed. Entries are visited in sorted path order,
+    /// compared component by component, so the result does not depend on
+    /// the order the file system lists them in or on the directory's own
+    /// location. Permissions and timestamps are not included.
+    ///
+    /// The digest is 64-bit FNV-1a, with components joined by `/`, so it
+    /// is the same across runs, platforms and releases of this crate for
+    /// the same tree (except for names that are not valid Unicode outside
+    /// of Unix). It is suitable as a cache key, but not against someone
+    /// deliberately constructing collisions. Every file is read in full.
+    ///
+    /// # Errors
+    ///
+    /// If any directory, file or link inside of it can not be read, the
+    /// underlying error is returned.
+    ///
+    /// If the tree is nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep, an error with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let first = TempDir::new("example")?;
+    /// let second = TempDir::new("example")?;
+    /// fs::write(first.path().join("output.txt"), "42")?;
+    /// fs::write(second.path().join("output.txt"), "42")?;
+    /// assert_eq!(first.content_hash()?, second.content_hash()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_hash(&self) -> io::Result<u64> {
+        digest::content_hash(self.path(), self.max_depth)
+    }
+
+    /// Sets the permissions of everything inside the temporary directory
+    /// to `mode`, on Unix.
+    ///
+    /// Every file and subdirectory gets exactly `mode`, like `chmod -R`
+    /// without touching the temporary directory itself. Symbolic links are
+    /// skipped, since changing their permissions would change their
+    /// targets, which may lie outside the directory. Subdirectories are
+    /// changed only after everything inside them, so a `mode` without
+    /// search permission doesn't stop the walk.
+    ///
+    /// Stops at the first error, leaving entries not yet visited as they
+    /// were.
+    ///
+    /// # Errors
+    ///
+    /// Any error reading the tree or setting permissions is returned.
+    /// If the tree is nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep, an error with `ErrorKind::InvalidData` is
+    /// returned.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("fixtures")?;
+    /// tmp_dir.copy_from("tests/fixtures")?;
+    /// tmp_dir.set_permissions_recursive(0o755)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn set_permissions_recursive(&self, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut dirs = Vec::new();
+        walk::walk(self.path(), self.max_depth, |path, metadata| {
+            if metadata.is_dir() {
+                dirs.push(path.to_path_buf());
+                Ok(())
+            } else if metadata.file_type().is_symlink() {
+                Ok(())
+            } else {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            }
+        })?;
+        // Directories are found before their contents, so going backwards
+        // changes every directory after all of its subdirectories.
+        for dir in dirs.iter().rev() {
+            fs::set_permissions(dir, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    /// Changes the owner and group of the temporary directory, on Unix.
+    ///
+    /// `None` leaves the owner or group unchanged. Only the directory
+    /// itself is changed, not anything inside it. This is typically used
+    /// by a setup script running as root to hand a scratch directory to
+    /// a service user. Changing the owner usually requires elevated
+    /// privileges; a user can change the group to one they belong to.
+    ///
+    /// # Errors
+    ///
+    /// If the underlying `chown` call fails, for instance with
+    /// `ErrorKind::PermissionDenied`, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("service")?;
+    /// tmp_dir.chown(Some(1000), None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        std::os::unix::fs::chown(self.path(), uid, gid)
+    }
+
+    /// Returns the number of bytes available to the current user on the
+    /// file system holding the temporary directory.
+    ///
+    /// Checking this before writing a lot of data allows failing early
+    /// instead of running out of space halfway through. The figure is a
+    /// snapshot: other processes may use up the space in the meantime,
+    /// and quotas may limit writes further. It comes from `statvfs` on
+    /// Unix and `GetDiskFreeSpaceExW` on Windows.
+    ///
+    /// # Errors
+    ///
+    /// If the file system can not be queried, for example because the
+    /// directory has been removed, `Err` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let needed = 64 * 1024 * 1024;
+    /// if tmp_dir.available_space()? < needed {
+    ///     return Err(io::Error::new(io::ErrorKind::Other, "not enough scratch space"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn available_space(&self) -> io::Result<u64> {
+        available_space(self.path())
+    }
+
+    /// Recursively copies the contents of the directory `src` into the
+    /// temporary directory, preserving their relative layout.
+    ///
+    /// Subdirectories are created as needed and files are copied with
+    /// [`fs::copy`], replacing any file already at the destination.
+    /// Symbolic links are never traversed: a link to a file is copied as
+    /// a regular file holding the contents of its target, while links to
+    /// directories and dangling links are skipped. This keeps a link
+    /// cycle in `src` from being copied forever.
+    ///
+    /// [`fs::copy`]: http://doc.rust-lang.org/std/fs/fn.copy.html
+    ///
+    /// # Errors
+    ///
+    /// If `src` is not a directory, an error with
+    /// `ErrorKind::NotADirectory` is returned. Any error reading `src`
+    /// or writing into the temporary directory is returned as well,
+    /// possibly after part of the tree has been copied. If `src` is
+    /// nested more than [`TempDirBuilder::max_depth`] directories deep,
+    /// an error with `ErrorKind::InvalidData` is returned once the
+    /// copy reaches that depth.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.copy_from("tests/fixtures")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn copy_from<P: AsRef<Path>>(&self, src: P) -> io::Result<()> {
+        let src = src.as_ref();
+        if !fs::metadata(src)?.is_dir() {
+            return Err(Error::new(ErrorKind::NotADirectory,
+                                  format!("{} is not a directory", src.display())));
+        }
+
+        copy_tree(src, self.path(), self.max_depth)
+    }
+
+    /// Removes everything inside the temporary directory, leaving the
+    /// directory itself in place.
+    ///
+    /// This lets one `TempDir` be reused for several runs that each need
+    /// an empty directory, without creating a new one. Subdirectories are
+    /// removed with their contents; symbolic links are removed without
+    /// touching their targets.
+    ///
+    /// # Errors
+    ///
+    /// The first error reading the directory or removing an entry is
+    /// returned, leaving the remaining entries in place. If the tree is
+    /// deeper than [`TempDirBuilder::max_depth`] allows, an error with
+    /// `ErrorKind::InvalidData` is returned before anything is removed.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// for _ in 0..3 {
+    ///     File::create(tmp_dir.path().join("output.txt"))?;
+    ///     // ... run one iteration ...
+    ///     tmp_dir.clear()?;
+    ///     assert!(tmp_dir.is_empty()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear(&self) -> io::Result<()> {
+        walk::walk(self.path(), self.max_depth, |_, _| Ok(()))?;
+        for entry in fs::read_dir(self.path())? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                cleanup_dir(&path)?;
+            } else if let Err(e) = fs::remove_file(&path) {
+                // Windows removes symlinks to directories as directories.
+                if !(cfg!(windows) && file_type.is_symlink()) {
+                    return Err(e);
+                }
+                fs::remove_dir(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the temporary directory to `dest`, keeping it and its
+    /// contents instead of deleting them.
+    ///
+    /// This first tries `fs::rename`, which atomically moves the whole
+    /// directory. `dest` must not exist, or on some platforms must be an
+    /// empty directory. If the rename fails because `dest` is on a
+    /// different file system, the directory is instead copied into a
+    /// newly created `dest` and then removed.
+    ///
+    /// The fallback is not atomic: other processes can observe `dest`
+    /// while it is only partly populated, and links are handled as by
+    /// [`copy_from`], so links to directories are not copied.
+    ///
+    /// [`copy_from`]: struct.TempDir.html#method.copy_from
+    ///
+    /// # Errors
+    ///
+    /// If the directory can not be renamed, or the fallback fails to
+    /// create `dest`, copy into it or remove the original, `Err` is
+    /// returned. In every case the original directory is left in place
+    /// rather than deleted, so its contents are never lost, and a partial
+    /// copy at `dest` is not cleaned up. Keep a copy of [`path`] to find
+    /// the directory again afterwards.
+    ///
+    /// [`path`]: struct.TempDir.html#method.path
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("build")?;
+    /// File::create(tmp_dir.path().join("output.bin"))?;
+    /// tmp_dir.persist_to("target/release-artifacts")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn persist_to<P: AsRef<Path>>(mut self, dest: P) -> io::Result<()> {
+        let dest = dest.as_ref();
+        // The caller wants the contents kept, so the directory must not be
+        // removed on drop, whether it moves or an error leaves it here.
+        self.keep = true;
+        match fs::rename(self.path(), dest) {
+            Err(ref e) if e.kind() == ErrorKind::CrossesDevices => {}
+            result => return result,
+        }
+
+        fs::create_dir(dest)?;
+        copy_tree(self.path(), dest, self.max_depth)?;
+        // The copy is complete, so the original goes with everything in
+        // it, whatever `cleanup_mode` says.
+        remove_with(self.fs.as_deref(), CleanupMode::Recursive, self.path())
+    }
+
+    /// Moves the file at the relative path `rel` inside the temporary
+    /// directory to `dest`, replacing `dest` atomically if it exists.
+    ///
+    /// This is the write-then-rename idiom: write the complete file in the
+    /// temporary directory, then commit it, so that readers of `dest` see
+    /// either the old file or the whole new one, never a partial write.
+    /// The temporary directory should be on the same file system as
+    /// `dest` for the move to be a plain `fs::rename`.
+    ///
+    /// If it isn't, the file is instead copied to a new file next to
+    /// `dest`, flushed to disk and renamed over `dest`, which keeps the
+    /// replacement atomic, and then the original is removed. The copy
+    /// gets default permissions rather than those of the original.
+    ///
+    /// `rel` is resolved as by [`try_child`].
+    ///
+    /// [`try_child`]: struct.TempDir.html#method.try_child
+    ///
+    /// # Errors
+    ///
+    /// If `rel` is absolute or climbs out of the temporary directory with
+    /// `..`, or does not name a regular file, an error with
+    /// `ErrorKind::InvalidInput` is returned. Errors renaming or copying
+    /// the file are returned as they occur; if the copy fails, `dest` is
+    /// left as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let scratch = TempDir::new_in("target", "config")?;
+    /// fs::write(scratch.path().join("settings.toml"), "level = 3\n")?;
+    /// scratch.commit_file("settings.toml", "target/settings.toml")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn commit_file<P: AsRef<Path>>(&self, rel: &str, dest: P) -> io::Result<()> {
+        let src = self.contained_path(Path::new(rel))?;
+        let dest = dest.as_ref();
+        if !fs::symlink_metadata(&src)?.is_file() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("{} is not a regular file", src.display())));
+        }
+        match fs::rename(&src, dest) {
+            Err(ref e) if e.kind() == ErrorKind::CrossesDevices => {}
+            result => return result,
+        }
+
+        let staged = copy_next_to(&src, dest)?;
+        if let Err(e) = fs::rename(&staged, dest) {
+            let _ = fs::remove_file(&staged);
+            return Err(e);
+        }
+        fs::remove_file(&src)
+    }
+
+    /// Unwraps the [`Path`] contained in the `TempDir` and
+    /// returns it. This destroys the `TempDir` without deleting the
+    /// directory represented by the returned `Path`.
+    ///
+    /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    ///
+    /// // Convert `tmp_dir` into a `Path`, destroying the `TempDir`
+    /// // without deleting the directory.
+    /// let tmp_path = tmp_dir.into_path();
+    ///
+    /// // Delete the temporary directory ourselves.
+    /// fs::remove_dir_all(tmp_path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_path(mut self) -> PathBuf {
+        self.path.take().unwrap()
+    }
+
+    /// Unwraps the [`Path`] contained in the `TempDir` like [`into_path`],
+    /// together with a closure that removes the directory when called.
+    ///
+    /// The directory is no longer tied to the handle's lifetime, but the
+    /// closure can be stored or registered elsewhere to delete it later,
+    /// for example at the end of a test suite. Calling it behaves like
+    /// [`close`], including respecting [`disable_cleanup`] and removing
+    /// through a custom [`FsProvider`]. If it is never called, the
+    /// directory is left in place.
+    ///
+    /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
+    /// [`into_path`]: struct.TempDir.html#method.into_path
+    /// [`close`]: struct.TempDir.html#method.close
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    /// [`FsProvider`]: trait.FsProvider.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let (path, cleanup) = tmp_dir.into_path_with_cleanup();
+    /// assert!(path.exists());
+    ///
+    /// // ... later ...
+    /// cleanup()?;
+    /// assert!(!path.exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_path_with_cleanup(mut self) -> (PathBuf, impl FnOnce() -> io::Result<()>) {
+        let path = self.path.take().unwrap();
+        let keep = self.keep;
+        let fs = self.fs.take();
+        let mode = self.cleanup_mode;
+        let target = path.clone();
+        (path, move || {
+            if keep {
+                return Ok(());
+            }
+            remove_with(fs.as_deref(), mode, &target)
+        })
+    }
+
+    /// Takes the `TempDir` apart into its path and whether this crate
+    /// created the directory, without deleting it.
+    ///
+    /// Like [`into_path`], this hands responsibility for the directory
+    /// to the caller. The flag is `false` for directories adopted with
+    /// [`TempDir::from_existing_path`] or deserialized, so code that
+    /// takes over cleanup can still leave those in place, as
+    /// [`close_owned`] does.
+    ///
+    /// [`into_path`]: struct.TempDir.html#method.into_path
+    /// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+    /// [`close_owned`]: struct.TempDir.html#method.close_owned
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let (path, created) = tmp_dir.into_parts();
+    /// assert!(created);
+    /// if created {
+    ///     fs::remove_dir_all(path)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_parts(mut self) -> (PathBuf, bool) {
+        (self.path.take().unwrap(), self.created_by_us)
+    }
+
+    /// Unwraps the [`Path`] contained in the `TempDir` like
+    /// [`into_path`], first flushing the parent directory to disk so that
+    /// the kept directory survives a crash.
+    ///
+    /// On Unix this opens the parent directory and calls `sync_all` on
+    /// it, which makes the directory's entry in its parent durable. On
+    /// other platforms directories can not be synced this way and
+    /// nothing extra is done. The contents of the directory are not
+    /// synced.
+    ///
+    /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
+    /// [`into_path`]: struct.TempDir.html#method.into_path
+    ///
+    /// # Errors
+    ///
+    /// If the parent directory can not be opened or synced, `Err` is
+    /// returned. The directory is kept on disk either way, just without
+    /// the guarantee that it survives a crash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let fixture = tmp_dir.into_path_synced()?;
+    /// assert!(fixture.exists());
+    /// # fs::remove_dir_all(fixture)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_path_synced(self) -> io::Result<PathBuf> {
+        // Released first, so that a failed sync can't remove it on drop.
+        let path = self.into_path();
+        #[cfg(unix)]
+        {
+            // Every managed path has a parent, as `base` relies on.
+            fs::File::open(path.parent().unwrap())?.sync_all()?;
+        }
+        Ok(path)
+    }
+
+    /// Prevents the directory from being deleted when the `TempDir` is
+    /// dropped.
+    ///
+    /// Unlike [`into_path`], this works through a mutable reference, so
+    /// it can be called conditionally, for example to keep a test's
+    /// scratch directory around only when the test fails. The handle
+    /// keeps working as before, and calling [`close`] afterwards is a
+    /// no-op that returns `Ok(())` without deleting anything.
+    ///
+    /// [`into_path`]: struct.TempDir.html#method.into_path
+    /// [`close`]: struct.TempDir.html#method.close
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let mut tmp_dir = TempDir::new("example")?;
+    /// let tmp_path = tmp_dir.path().to_owned();
+    ///
+    /// let failed = true;
+    /// if failed {
+    ///     tmp_dir.disable_cleanup();
+    /// }
+    /// drop(tmp_dir);
+    ///
+    /// assert!(tmp_path.exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn disable_cleanup(&mut self) {
+        self.keep = true;
+    }
+
+    /// Keeps the directory when the `TempDir` is dropped, the same as
+    /// [`disable_cleanup`].
+    ///
+    /// This reads naturally in the scope guard pattern, where the
+    /// directory is removed on every path out of a function except the
+    /// ones that explicitly keep it.
+    ///
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let mut scratch = TempDir::new("artifacts")?;
+    /// fs::write(scratch.path().join("log.txt"), "step 1 failed")?;
+    /// let passed = false;
+    /// if !passed {
+    ///     eprintln!("artifacts kept in {}", scratch.path().display());
+    ///     scratch.keep();
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keep(&mut self) {
+        self.disable_cleanup();
+    }
+
+    /// Closes and removes the temporary directory, returing a `Result`.
+    ///
+    /// Although `TempDir` removes the directory on drop, in the destructor
+    /// any errors are ignored. To detect errors cleaning up the temporary
+    /// directory, call `close` instead.
+    ///
+    /// # Errors
+    ///
+    /// This function may return a variety of [`std::io::Error`]s that result from deleting
+    /// the files and directories contained with the temporary directory,
+    /// as well as from deleting the temporary directory itself. These errors
+    /// may be platform specific. On Windows, where files are often briefly
+    /// locked by other processes, removal is retried a few times before an
+    /// error is returned.
+    ///
+    /// Removal is only ever attempted once: whether or not `close`
+    /// succeeds, the directory is not removed again when the `TempDir`
+    /// is dropped.
+    ///
+    /// [`std::io::Error`]: http://doc.rust-lang.org/std/io/struct.Error.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::Write;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// // Create a directory inside of `std::env::temp_dir()`, named with
+    /// // the prefix, "example".
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let file_path = tmp_dir.path().join("my-temporary-note.txt");
+    /// let mut tmp_file = File::create(file_path)?;
+    /// writeln!(tmp_file, "Brian was here. Briefly.")?;
+    ///
+    /// // By closing the `TempDir` explicitly we can check that it has
+    /// // been deleted successfully. If we don't close it explicitly,
+    /// // the directory will still be deleted when `tmp_dir` goes out
+    /// // of scope, but we won't know whether deleting the directory
+    /// // succeeded.
+    /// drop(tmp_file);
+    /// tmp_dir.close()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close(self) -> io::Result<()> {
+        self.close_path().map(|_| ())
+    }
+
+    /// Closes and removes the temporary directory on Tokio's blocking
+    /// thread pool, returning a future that resolves once it is gone.
+    ///
+    /// Removing a large directory can take a long time, and doing it
+    /// with [`close`] from an async task would block the executor. The
+    /// removal is started when the returned future is first polled,
+    /// which must happen within a Tokio runtime. If the future is dropped
+    /// without being polled, for example when a `select!` picks another
+    /// branch, the directory is removed synchronously on drop, like any
+    /// other `TempDir`. Available with the `tokio` feature.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`close`] are returned. If the blocking task
+    /// panics or is cancelled, an error with `ErrorKind::Other` is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```edition2018
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # async fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.close_async().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn close_async(self) -> CloseAsync {
+        CloseAsync::new(self)
+    }
+
+    /// Closes and removes the temporary directory like [`close`], but
+    /// returns the path of the removed directory on success.
+    ///
+    /// If cleanup has been disabled with [`disable_cleanup`], nothing
+    /// is removed and the path of the retained directory is returned.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`close`] are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let removed = tmp_dir.close_path()?;
+    /// println!("removed {}", removed.display());
+    /// assert!(!removed.exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_path(mut self) -> io::Result<PathBuf> {
+        // Prevent the Drop impl from removing the dir a second time.
+        let path = self.path.take().unwrap();
+        if self.keep {
+            return Ok(path);
+        }
+
+        self.remove_all(&path).map(|_| path)
+    }
+
+    /// Closes and removes the temporary directory like [`close`], but only
+    /// if this crate created it.
+    ///
+    /// Directories adopted with [`TempDir::from_existing_path`] (or
+    /// deserialized) are left in place and `Ok(())` is returned, so code
+    /// that is handed an arbitrary `TempDir` can't wipe a directory that
+    /// existed before. Cleanup disabled with [`disable_cleanup`] is
+    /// respected as with `close`.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    /// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`close`] are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// fs::create_dir_all("existing")?;
+    /// let adopted = TempDir::from_existing_path("existing".into())?;
+    /// adopted.close_owned()?;
+    /// assert!(fs::metadata("existing")?.is_dir());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_owned(mut self) -> io::Result<()> {
+        if !self.created_by_us {
+            // Forget the path so that the Drop impl leaves it alone too.
+            self.path.take();
+            return Ok(());
+        }
+        self.close()
+    }
+
+    /// Closes and removes the temporary directory, failing if it still
+    /// contains any entries.
+    ///
+    /// Unlike [`close`], which removes everything inside the directory,
+    /// this only removes the directory itself with `fs::remove_dir`. It
+    /// is useful for asserting that code using the directory has cleaned
+    /// up after itself. If the directory is not empty, or can't be read,
+    /// an error is returned and the directory is left on disk with its
+    /// contents, so the leftovers can be inspected. Removing it is then
+    /// up to the caller.
+    ///
+    /// If cleanup has been disabled with [`disable_cleanup`], the check
+    /// is still made but nothing is removed.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    ///
+    /// # Errors
+    ///
+    /// If the directory is not empty, an error with
+    /// `ErrorKind::DirectoryNotEmpty` is returned. Errors reading or
+    /// removing the directory are returned as they occur.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::{self, File};
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// let file_path = tmp_dir.path().join("scratch.txt");
+    /// File::create(&file_path)?;
+    /// // ... use the file ...
+    /// fs::remove_file(&file_path)?;
+    /// tmp_dir.close_if_empty()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_if_empty(mut self) -> io::Result<()> {
+        match self.is_empty() {
+            Ok(true) => {}
+            Ok(false) => {
+                self.keep = true;
+                return Err(io::Error::new(
+                    ErrorKind::DirectoryNotEmpty,
+                    format!("temporary directory {} is not empty", self.path().display()),
+                ));
+            }
+            Err(e) => {
+                self.keep = true;
+                return Err(e);
+            }
+        }
+
+        // Prevent the Drop impl from removing the dir a second time.
+        let path = self.path.take().unwrap();
+        if self.keep {
+            return Ok(());
+        }
+
+        remove_with(self.fs.as_deref(), CleanupMode::Empty, &path)
+    }
+
+    /// Closes and removes the temporary directory like [`close`], but
+    /// reports every entry that could not be removed instead of a single
+    /// error.
+    ///
+    /// `close` stops at the first failure, which says little about what
+    /// is left behind. This instead walks the tree itself and keeps going
+    /// past failures, removing whatever it can, so the result lists each
+    /// locked or protected file along with its error. A directory that
+    /// could not be emptied is not listed again for failing to be removed
+    /// itself. Symbolic links are removed, never followed.
+    ///
+    /// The walk always uses the real file system, even when a
+    /// [`TempDirBuilder::fs_provider`] is installed, and is slower than
+    /// `close` for large trees. If cleanup has been disabled with
+    /// [`disable_cleanup`], nothing is removed and `Ok(())` is returned.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    /// [`TempDirBuilder::fs_provider`]: struct.TempDirBuilder.html#method.fs_provider
+    /// [`disable_cleanup`]: struct.TempDir.html#method.disable_cleanup
+    ///
+    /// # Errors
+    ///
+    /// The path and error of every entry that could not be read or
+    /// removed are returned, in the order they were encountered. A
+    /// directory nested more than [`TempDirBuilder::max_depth`]
+    /// directories deep is reported with `ErrorKind::InvalidData` and
+    /// left in place with its contents.
+    ///
+    /// [`TempDirBuilder::max_depth`]: struct.TempDirBuilder.html#method.max_depth
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.create_file("output/log.txt")?;
+    /// if let Err(failures) = tmp_dir.close_verbose() {
+    ///     for (path, err) in failures {
+    ///         eprintln!("could not remove {}: {}", path.display(), err);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_verbose(mut self) -> Result<(), Vec<(PathBuf, io::Error)>> {
+        // Prevent the Drop impl from removing the dir a second time.
+        let path = self.path.take().unwrap();
+        if self.keep {
+            return Ok(());
+        }
+
+        let failures = match self.cleanup_mode {
+            CleanupMode::Recursive => remove_tree_reporting(&path, self.max_depth),
+            CleanupMode::Empty => {
+                match remove_with(self.fs.as_deref(), CleanupMode::Empty, &path) {
+                    Ok(()) => Vec::new(),
+                    Err(e) => vec![(path, e)],
+                }
+            }
+        };
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    /// Closes and removes the temporary directory like [`close`], retrying
+    /// until it succeeds or `timeout` has elapsed.
+    ///
+    /// Removal fails while another process holds files in the directory
+    /// open, such as a subprocess that has not exited yet on Windows or a
+    /// stale handle on NFS. This keeps trying, waiting 10 milliseconds
+    /// after the first failure and doubling the wait up to half a second
+    /// between later attempts, so that the directory is removed as soon
+    /// as it is released. At least one attempt is always made, even with
+    /// a zero timeout.
+    ///
+    /// [`close`]: struct.TempDir.html#method.close
+    ///
+    /// # Errors
+    ///
+    /// If the directory still can't be removed when the timeout is
+    /// reached, the error from the last attempt is returned and the
+    /// directory is left behind.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::new("example")?;
+    /// tmp_dir.close_timeout(Duration::from_secs(5))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close_timeout(mut self, timeout: Duration) -> io::Result<()> {
+        const MAX_DELAY: Duration = Duration::from_millis(500);
+
+        // Prevent the Drop impl from removing the dir a second time.
+        let path = self.path.take().unwrap();
+        if self.keep {
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_millis(10);
+        let mut first = true;
+        loop {
+            match self.remove_all(&path) {
+                Ok(()) => return Ok(()),
+                // An earlier attempt got as far as removing the directory
+                // before reporting an error.
+                Err(ref e) if !first && e.kind() == ErrorKind::NotFound => return Ok(()),
+                Err(e) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(e);
+                    }
+                    thread::sleep(cmp::min(delay, deadline - now));
+                    delay = cmp::min(delay * 2, MAX_DELAY);
+                    first = false;
+                }
+            }
+        }
+    }
+}
+
+/// A builder for configuring and creating a [`TempDir`].
+///
+/// A `TempDirBuilder` holds the settings used to name a new temporary
+/// directory. Nothing touches the file system until one of the
+/// terminal methods, [`tempdir`] or [`tempdir_in`], is called, so a
+/// configured builder can be cloned and reused as a template for
+/// creating many directories.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+/// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+///
+/// # Examples
+///
+/// ```
+/// use tempdir::TempDirBuilder;
+///
+/// # use std::io;
+/// # fn run() -> Result<(), io::Error> {
+/// let mut template = TempDirBuilder::new();
+/// template.prefix("example").rand_bytes(6);
+///
+/// let first = template.tempdir()?;
+/// let second = template.tempdir()?;
+/// assert!(first.path() != second.path());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TempDirBuilder {
+    prefix: String,
+    sanitize_prefix: bool,
+    suffix: String,
+    separator: Option<char>,
+    dot_without_prefix: bool,
+    rand_bytes: usize,
+    alphabet: Option<Vec<char>>,
+    max_retries: u32,
+    max_name_len: usize,
+    #[cfg(feature = "rand")]
+    rng: Option<Arc<Mutex<Box<dyn Rng + Send>>>>,
+    #[cfg(feature = "rand")]
+    default_rng: random::Cached,
+    keep_on_panic: bool,
+    mode: u32,
+    setgid: bool,
+    restrict_to_user: bool,
+    require_sticky: bool,
+    check_base: bool,
+    create_parents: bool,
+    naming: Naming,
+    include_pid: bool,
+    on_cleanup_error: Option<CleanupErrorHandler>,
+    panic_on_cleanup_error: bool,
+    cleanup_mode: CleanupMode,
+    fs: Option<Arc<dyn FsProvider>>,
+    bases: Vec<PathBuf>,
+    try_next_base: bool,
+    template: Option<String>,
+    private: bool,
+    collision_backoff: Duration,
+    max_depth: usize,
+    name_fn: Option<NameFn>,
+}
+
+type NameFn = Arc<Mutex<Box<dyn FnMut() -> String + Send>>>;
+
+/// The scheme used by a [`TempDirBuilder`] to generate the part of a
+/// directory name between the prefix and the suffix.
+///
+/// More schemes may be added in the future, so matches on `Naming` must
+/// include a wildcard arm.
+///
+/// [`TempDirBuilder`]: struct.TempDirBuilder.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Naming {
+    /// Random characters, as many as configured with
+    /// [`TempDirBuilder::rand_bytes`] and drawn from
+    /// [`TempDirBuilder::rand_alphabet`]. Each attempt draws a new name.
+    ///
+    /// [`TempDirBuilder::rand_bytes`]: struct.TempDirBuilder.html#method.rand_bytes
+    /// [`TempDirBuilder::rand_alphabet`]: struct.TempDirBuilder.html#method.rand_alphabet
+    #[default]
+    Random,
+    /// Increasing integers starting at `0`, giving names like `prefix.0`,
+    /// `prefix.1` and so on. The first name not already taken is used.
+    ///
+    /// Sequential names are trivial to predict, so this is meant for
+    /// local debugging rather than directories in shared locations.
+    Sequential,
+}
+
+/// How a [`TempDir`] removes its directory, as chosen with
+/// [`TempDirBuilder::cleanup_mode`].
+///
+/// More modes may be added in the future, so matches on `CleanupMode`
+/// must include a wildcard arm.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`TempDirBuilder::cleanup_mode`]: struct.TempDirBuilder.html#method.cleanup_mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CleanupMode {
+    /// Remove the directory and everything inside it.
+    #[default]
+    Recursive,
+    /// Remove the directory only if it is empty, like `fs::remove_dir`.
+    /// Otherwise removal fails, with `ErrorKind::DirectoryNotEmpty` on
+    /// most platforms, and the directory and its contents are left in
+    /// place.
+    Empty,
+}
+
+impl Default for TempDirBuilder {
+    fn default() -> TempDirBuilder {
+        TempDirBuilder {
+            prefix: String::new(),
+            sanitize_prefix: false,
+            suffix: String::new(),
+            separator: Some('.'),
+            dot_without_prefix: false,
+            rand_bytes: DEFAULT_RAND_CHARS,
+            alphabet: None,
+            max_retries: NUM_RETRIES,
+            max_name_len: MAX_NAME_LEN,
+            #[cfg(feature = "rand")]
+            rng: None,
+            #[cfg(feature = "rand")]
+            default_rng: random::Cached::default(),
+            keep_on_panic: false,
+            mode: 0o700,
+            setgid: false,
+            restrict_to_user: false,
+            require_sticky: false,
+            check_base: false,
+            create_parents: false,
+            naming: Naming::Random,
+            include_pid: false,
+            on_cleanup_error: None,
+            panic_on_cleanup_error: false,
+            cleanup_mode: CleanupMode::Recursive,
+            fs: None,
+            bases: Vec::new(),
+            try_next_base: false,
+            template: None,
+            private: false,
+            collision_backoff: Duration::from_secs(0),
+            max_depth: MAX_DEPTH,
+            name_fn: None,
+        }
+    }
+}
+
+impl fmt::Debug for TempDirBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("TempDirBuilder");
+        d.field("prefix", &self.prefix)
+            .field("sanitize_prefix", &self.sanitize_prefix)
+            .field("suffix", &self.suffix)
+            .field("separator", &self.separator)
+            .field("dot_without_prefix", &self.dot_without_prefix)
+            .field("rand_bytes", &self.rand_bytes)
+            .field("rand_alphabet", &self.alphabet)
+            .field("max_retries", &self.max_retries)
+            .field("max_name_len", &self.max_name_len)
+            .field("collision_backoff", &self.collision_backoff)
+            .field("max_depth", &self.max_depth);
+        #[cfg(feature = "rand")]
+        d.field("custom_rng", &self.rng.is_some());
+        d.field("keep_on_panic", &self.keep_on_panic)
+            .field("mode", &format_args!("{:#o}", self.mode))
+            .field("setgid", &self.setgid)
+            .field("restrict_to_user", &self.restrict_to_user)
+            .field("require_sticky", &self.require_sticky)
+            .field("check_base", &self.check_base)
+            .field("create_parents", &self.create_parents)
+            .field("naming", &self.naming)
+            .field("name_fn", &self.name_fn.is_some())
+            .field("include_pid", &self.include_pid)
+            .field("on_cleanup_error", &self.on_cleanup_error.is_some())
+            .field("panic_on_cleanup_error", &self.panic_on_cleanup_error)
+            .field("cleanup_mode", &self.cleanup_mode)
+            .field("custom_fs", &self.fs.is_some())
+            .field("bases", &self.bases)
+            .field("try_next_base", &self.try_next_base)
+            .field("template", &self.template)
+            .field("private", &self.private)
+            .finish()
+    }
+}
+
+impl TempDirBuilder {
+    /// Creates a new builder with an empty prefix and suffix and the
+    /// default number of random characters.
+    pub fn new() -> TempDirBuilder {
+        TempDirBuilder::default()
+    }
+
+    /// Sets the prefix the directory name will start with.
+    ///
+    /// Defaults to the empty string.
+    ///
+    /// # Errors
+    ///
+    /// A prefix containing a path separator or a NUL byte is not
+    /// rejected here, but causes [`tempdir`] and [`tempdir_in`] to fail
+    /// with `ErrorKind::InvalidInput`, unless [`sanitize_prefix`] is
+    /// enabled.
+    ///
+    /// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    /// [`sanitize_prefix`]: struct.TempDirBuilder.html#method.sanitize_prefix
+    pub fn prefix(&mut self, prefix: &str) -> &mut TempDirBuilder {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Sets whether characters that don't belong in a file name are
+    /// replaced in the prefix instead of being rejected.
+    ///
+    /// When enabled, each `/`, `\`, NUL byte, ASCII control character
+    /// and character Windows doesn't allow in file names (`<>:"|?*`) in
+    /// the prefix is replaced with `_` before the name is built, on every
+    /// platform, so that a prefix taken from user input such as a test
+    /// name always works and gives the same name everywhere.
+    /// [`TempDir::prefix`] returns the replaced prefix.
+    ///
+    /// Defaults to `false`, rejecting prefixes with path separators or
+    /// NUL bytes.
+    ///
+    /// [`TempDir::prefix`]: struct.TempDir.html#method.prefix
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("tests::io/read: \"large\"")
+    ///     .sanitize_prefix(true)
+    ///     .tempdir()?;
+    /// assert_eq!(tmp_dir.prefix(), "tests__io_read_ _large_");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sanitize_prefix(&mut self, sanitize_prefix: bool) -> &mut TempDirBuilder {
+        self.sanitize_prefix = sanitize_prefix;
+        self
+    }
+
+    /// Sets the suffix the directory name will end with.
+    ///
+    /// The suffix is appended to the random characters exactly as
+    /// given, so a suffix like `".tmp"` produces a name such as
+    /// `prefix.a1B2c3D4e5F6.tmp`, while `"-work"` produces
+    /// `prefix.a1B2c3D4e5F6-work`. No separator is inserted before it.
+    ///
+    /// Defaults to the empty string.
+    ///
+    /// # Errors
+    ///
+    /// A suffix containing a path separator or a NUL byte is not rejected
+    /// here, but causes [`tempdir`] and [`tempdir_in`] to fail with
+    /// `ErrorKind::InvalidInput` instead of creating nested directories.
+    ///
+    /// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    pub fn suffix(&mut self, suffix: &str) -> &mut TempDirBuilder {
+        self.suffix = suffix.to_owned();
+        self
+    }
+
+    /// Sets a template for the whole directory name, in which the first
+    /// `{}` is replaced by the random part.
+    ///
+    /// This gives full control over the layout for tools that parse the
+    /// names: `"build-{}-tmp"` produces names such as
+    /// `build-a1B2c3D4e5F6-tmp`. If the template has no `{}`, the random
+    /// part is appended to it. A template takes the place of the prefix,
+    /// separator and suffix, and of [`dot_without_prefix`], which then no
+    /// longer affect the name; [`TempDir::prefix`] still reports the
+    /// configured prefix.
+    ///
+    /// Defaults to no template.
+    ///
+    /// [`dot_without_prefix`]: struct.TempDirBuilder.html#method.dot_without_prefix
+    /// [`TempDir::prefix`]: struct.TempDir.html#method.prefix
+    ///
+    /// # Errors
+    ///
+    /// A template with more than one `{}`, or containing a path separator
+    /// or a NUL byte, is not rejected here, but causes [`tempdir`] and
+    /// [`tempdir_in`] to fail with `ErrorKind::InvalidInput`.
+    ///
+    /// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new().template("build-{}-tmp").rand_bytes(6).tempdir()?;
+    /// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with("build-") && name.ends_with("-tmp"));
+    /// assert_eq!(name.len(), "build--tmp".len() + 6);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn template(&mut self, template: &str) -> &mut TempDirBuilder {
+        self.template = Some(template.to_owned());
+        self
+    }
+
+    /// Sets the character placed between the prefix and the random part
+    /// of the name, or `None` to join them directly.
+    ///
+    /// With the default of `Some('.')` names look like
+    /// `prefix.RANDOM`; with `None` they look like `prefixRANDOM`. No
+    /// separator is inserted when the prefix or the random part is empty,
+    /// so a name never starts with it.
+    ///
+    /// A separator that is a path separator or NUL is rejected with
+    /// `ErrorKind::InvalidInput` when the directory is created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("example")
+    ///     .separator(Some('-'))
+    ///     .tempdir()?;
+    /// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with("example-"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn separator(&mut self, separator: Option<char>) -> &mut TempDirBuilder {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets whether a name without a prefix starts with a dot.
+    ///
+    /// With an empty prefix the name normally begins with the random
+    /// characters, because a leading dot hides the directory from `ls`
+    /// and many file managers, which makes leaked directories easy to
+    /// overlook. Enabling this gives the traditional hidden `.RANDOM`
+    /// name instead. It has no effect when a prefix is set.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new().dot_without_prefix(true).tempdir()?;
+    /// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with('.'));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dot_without_prefix(&mut self, dot_without_prefix: bool) -> &mut TempDirBuilder {
+        self.dot_without_prefix = dot_without_prefix;
+        self
+    }
+
+    /// Sets the number of random characters placed between the prefix
+    /// and the suffix.
+    ///
+    /// Fewer characters give shorter names at the cost of more likely
+    /// collisions; more characters make names harder to guess in shared
+    /// temporary directories. With zero random characters the name is
+    /// just the prefix followed by the suffix, so only a single attempt
+    /// is made to create it.
+    ///
+    /// Defaults to [`DEFAULT_RAND_CHARS`].
+    ///
+    /// [`DEFAULT_RAND_CHARS`]: constant.DEFAULT_RAND_CHARS.html
+    pub fn rand_bytes(&mut self, rand_bytes: usize) -> &mut TempDirBuilder {
+        self.rand_bytes = rand_bytes;
+        self
+    }
+
+    /// Sets the characters that random names are drawn from.
+    ///
+    /// Each random character is sampled uniformly from `alphabet`. On a
+    /// case-insensitive file system names differing only in case collide,
+    /// so restricting the alphabet to lowercase letters and digits keeps
+    /// every character significant. Repeating a character in `alphabet`
+    /// makes it proportionally more likely.
+    ///
+    /// Defaults to the ASCII letters and digits.
+    ///
+    /// An empty alphabet, or one containing a path separator or NUL, is
+    /// rejected with `ErrorKind::InvalidInput` when the directory is
+    /// created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let lower: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect();
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("example")
+    ///     .rand_alphabet(&lower)
+    ///     .tempdir()?;
+    /// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+    /// assert_eq!(name, name.to_lowercase());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rand_alphabet(&mut self, alphabet: &[char]) -> &mut TempDirBuilder {
+        self.alphabet = Some(alphabet.to_vec());
+        self
+    }
+
+    /// Sets the maximum number of names to try before giving up.
+    ///
+    /// Each attempt generates a fresh random name; an attempt fails only
+    /// if a file or directory with that name already exists. Once the
+    /// limit is exhausted, creation fails with `ErrorKind::AlreadyExists`.
+    /// A limit of zero makes every creation fail without touching the
+    /// file system.
+    ///
+    /// Defaults to 2<sup>31</sup>.
+    ///
+    /// # Examples
+    ///
+    /// Code that handles running out of names can be tested by making
+    /// every attempt collide. A [`name_fn`] that always returns the name
+    /// of an existing directory does this on the real file system, and a
+    /// small limit keeps the test fast:
+    ///
+    /// [`name_fn`]: struct.TempDirBuilder.html#method.name_fn
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    /// use tempdir::{TempDir, TempDirBuilder};
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let base = TempDir::new("example")?;
+    /// std::fs::create_dir(base.path().join("taken"))?;
+    ///
+    /// let err = TempDirBuilder::new()
+    ///     .name_fn(|| "taken".to_owned())
+    ///     .max_retries(3)
+    ///     .tempdir_in(base.path())
+    ///     .unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_retries(&mut self, max_retries: u32) -> &mut TempDirBuilder {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how many directories deep the recursive helpers of the
+    /// created `TempDir` go before giving up.
+    ///
+    /// [`size`], [`entry_count`], [`list_files`], [`content_hash`],
+    /// [`set_permissions_recursive`], [`sync_all`], [`copy_from`],
+    /// [`clear`] and [`close_verbose`] walk the whole tree. None of them follow
+    /// symbolic links, so they can't loop, but a hostile process sharing
+    /// the base directory could still plant an absurdly deep tree. An
+    /// entry nested more than `max_depth` directories below the
+    /// directory (or below the source, for `copy_from`) makes them fail
+    /// with `ErrorKind::InvalidData` instead of working through it;
+    /// `clear` checks before removing anything, and `close_verbose`
+    /// reports the too-deep directories and removes the rest. Dropping
+    /// or closing the directory is not limited.
+    ///
+    /// Defaults to 1024, deeper than most systems allow paths to be.
+    ///
+    /// [`size`]: struct.TempDir.html#method.size
+    /// [`entry_count`]: struct.TempDir.html#method.entry_count
+    /// [`list_files`]: struct.TempDir.html#method.list_files
+    /// [`content_hash`]: struct.TempDir.html#method.content_hash
+    /// [`set_permissions_recursive`]: struct.TempDir.html#method.set_permissions_recursive
+    /// [`sync_all`]: struct.TempDir.html#method.sync_all
+    /// [`copy_from`]: struct.TempDir.html#method.copy_from
+    /// [`clear`]: struct.TempDir.html#method.clear
+    /// [`close_verbose`]: struct.TempDir.html#method.close_verbose
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut TempDirBuilder {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the longest time to sleep after a name turns out to be taken,
+    /// before trying the next one.
+    ///
+    /// Normally the next name is tried straight away. When many processes
+    /// create directories in the same place at once, sleeping for a
+    /// random fraction of `backoff` after each collision spreads their
+    /// attempts out instead of having them all hit the file system
+    /// together. Only collisions back off; other errors are still
+    /// returned immediately, and there is no sleep after the last
+    /// attempt.
+    ///
+    /// Defaults to zero, which never sleeps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("shared")
+    ///     .collision_backoff(Duration::from_millis(5))
+    ///     .tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn collision_backoff(&mut self, backoff: Duration) -> &mut TempDirBuilder {
+        self.collision_backoff = backoff;
+        self
+    }
+
+    /// Sets the maximum length in bytes of the directory name.
+    ///
+    /// Most file systems limit a single name to 255 bytes, and a long
+    /// prefix and suffix together with the random characters can exceed
+    /// that. Names longer than the limit are rejected with
+    /// `ErrorKind::InvalidInput` and a message giving both lengths,
+    /// rather than with whatever error the file system reports.
+    ///
+    /// Defaults to 255.
+    pub fn max_name_len(&mut self, max_name_len: usize) -> &mut TempDirBuilder {
+        self.max_name_len = max_name_len;
+        self
+    }
+
+    /// Sets the random number generator used to generate names.
+    ///
+    /// By default names are drawn from `rand::thread_rng()`, or, once a
+    /// builder is used for more than one directory, from a generator
+    /// seeded from it that the builder keeps for later creations.
+    /// Supplying a seeded generator instead makes the sequence of
+    /// generated names reproducible, which is useful for asserting on
+    /// exact paths in tests. Clones of this builder share the same
+    /// generator, so names drawn from one clone advance the others.
+    ///
+    /// Predictable names are easy for an attacker to claim ahead of
+    /// time, so a seeded generator should not be used for directories
+    /// in shared locations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate rand;
+    /// extern crate tempdir;
+    ///
+    /// use rand::{SeedableRng, XorShiftRng};
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    /// let tmp_dir = TempDir::builder().prefix("example").rng(rng).tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn rng<R: Rng + Send + 'static>(&mut self, rng: R) -> &mut TempDirBuilder {
+        self.rng = Some(Arc::new(Mutex::new(Box::new(rng))));
+        self
+    }
+
+    /// Sets whether the directory is kept when it is dropped while the
+    /// thread is panicking.
+    ///
+    /// This is useful for inspecting the contents of a scratch directory
+    /// after a failing test. When the directory is kept, its path is
+    /// printed to standard error so it can be found afterwards.
+    ///
+    /// Defaults to `false`.
+    pub fn keep_on_panic(&mut self, keep_on_panic: bool) -> &mut TempDirBuilder {
+        self.keep_on_panic = keep_on_panic;
+        self
+    }
+
+    /// Sets the permissions the directory is created with on Unix.
+    ///
+    /// The directory is created with exactly these permission bits,
+    /// further restricted by the process umask, so the default of
+    /// `0o700` keeps it private to the current user whatever the umask
+    /// allows. This is a no-op on Windows.
+    ///
+    /// Defaults to `0o700`.
+    pub fn mode(&mut self, mode: u32) -> &mut TempDirBuilder {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets whether the directory gets the set-group-ID bit on Unix.
+    ///
+    /// Files and directories created inside a setgid directory belong to
+    /// the directory's group rather than the creating user's primary
+    /// group, which helps several users in one group share a scratch
+    /// directory. The bit is added to the permissions chosen with
+    /// [`mode`] right after the directory is created, since `mkdir`
+    /// doesn't reliably honor it. This is a no-op on other platforms.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`mode`]: struct.TempDirBuilder.html#method.mode
+    pub fn setgid(&mut self, setgid: bool) -> &mut TempDirBuilder {
+        self.setgid = setgid;
+        self
+    }
+
+    /// Sets whether the directory is made accessible to the current user
+    /// only, whatever the platform.
+    ///
+    /// On Unix this clears the group and other bits of [`mode`], giving
+    /// at most `0o700`. On Windows, with the `windows-acl` feature, the
+    /// directory's inherited permissions are replaced right after it is
+    /// created by an access control list granting full control to the
+    /// current user and nobody else, which files and directories created
+    /// inside inherit. The user is looked up in the process token with
+    /// `OpenProcessToken` and `GetTokenInformation`, and the list is
+    /// built with `SetEntriesInAclW` and applied with
+    /// `SetNamedSecurityInfoW`. On other platforms this is a no-op.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`mode`]: struct.TempDirBuilder.html#method.mode
+    ///
+    /// # Errors
+    ///
+    /// On Windows without the `windows-acl` feature, creating the
+    /// directory fails with `ErrorKind::Unsupported` rather than leaving
+    /// it open to other users. If the access control list can't be
+    /// applied, the directory is removed and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("example")
+    ///     .restrict_to_user(true)
+    ///     .tempdir()?;
+    /// # drop(tmp_dir);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn restrict_to_user(&mut self, restrict_to_user: bool) -> &mut TempDirBuilder {
+        self.restrict_to_user = restrict_to_user;
+        self
+    }
+
+    /// Sets whether to create the directory inside a parent private to
+    /// this process, on Linux.
+    ///
+    /// Directories can't be created unlinked the way `O_TMPFILE` creates
+    /// files, so a process that is killed always leaks its temporary
+    /// directories. With this enabled, the first directory created in a
+    /// base makes a parent named `tempdir-private.PID.RANDOM` there, only
+    /// accessible to the current user, and every later one from this
+    /// process goes inside it. What a crashed process leaves behind is
+    /// then a single directory whose name tells which process made it,
+    /// so a sweeper can remove the parents of processes that are no
+    /// longer running without looking inside. The parent itself is not
+    /// removed when the process exits. Finding a free name for the
+    /// parent gives up after [`max_retries`] attempts, like the directory
+    /// itself.
+    ///
+    /// [`TempDir::base`] returns the private parent. On other platforms
+    /// this setting is ignored and directories are created in the base
+    /// as usual.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`TempDir::base`]: struct.TempDir.html#method.base
+    /// [`max_retries`]: struct.TempDirBuilder.html#method.max_retries
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let first = TempDirBuilder::new().prefix("example").private(true).tempdir()?;
+    /// let second = TempDirBuilder::new().prefix("example").private(true).tempdir()?;
+    /// assert_eq!(first.base(), second.base());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn private(&mut self, private: bool) -> &mut TempDirBuilder {
+        self.private = private;
+        self
+    }
+
+    /// Sets whether to refuse creating the directory inside a
+    /// world-writable base directory that lacks the sticky bit, on Unix.
+    ///
+    /// In such a directory any user can rename or delete entries created
+    /// by others, so a directory created there can be swapped for a
+    /// symbolic link or one controlled by an attacker. `/tmp` is normally
+    /// sticky; this catches misconfigured shared locations. The check is
+    /// skipped on other platforms.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("example")
+    ///     .require_sticky(true)
+    ///     .tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn require_sticky(&mut self, require_sticky: bool) -> &mut TempDirBuilder {
+        self.require_sticky = require_sticky;
+        self
+    }
+
+    /// Sets whether the base directory is checked before any name is
+    /// tried.
+    ///
+    /// Without the check, a missing or unusable base directory is only
+    /// noticed when creating the directory fails, with whatever error the
+    /// operating system reports for that. With it, the base is examined
+    /// first and a descriptive error naming it is returned: `NotFound`
+    /// if it does not exist, `NotADirectory` if it is not a directory,
+    /// and on Unix `PermissionDenied` if the current user can not create
+    /// entries in it. This costs an extra system call or two per
+    /// creation.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// let err = TempDirBuilder::new()
+    ///     .check_base(true)
+    ///     .tempdir_in("/does/not/exist")
+    ///     .unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::NotFound);
+    /// assert!(err.to_string().contains("base directory does not exist"));
+    /// ```
+    pub fn check_base(&mut self, check_base: bool) -> &mut TempDirBuilder {
+        self.check_base = check_base;
+        self
+    }
+
+    /// Sets whether a missing base directory, and any missing parents
+    /// of it, are created before the temporary directory itself.
+    ///
+    /// Only the base directory passed to [`tempdir_in`] is created this
+    /// way; the randomly named directory inside it is still created on
+    /// its own, so name collisions are detected and retried as usual.
+    /// Created parents are not removed when the `TempDir` is dropped.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    pub fn create_parents(&mut self, create_parents: bool) -> &mut TempDirBuilder {
+        self.create_parents = create_parents;
+        self
+    }
+
+    /// Sets the scheme used to generate the part of the name between the
+    /// prefix and the suffix.
+    ///
+    /// Defaults to [`Naming::Random`].
+    ///
+    /// [`Naming::Random`]: enum.Naming.html#variant.Random
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::{Naming, TempDir};
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// // Creates `example.0`, or `example.1` if that is taken, and so on.
+    /// let tmp_dir = TempDir::builder()
+    ///     .prefix("example")
+    ///     .naming(Naming::Sequential)
+    ///     .tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn naming(&mut self, naming: Naming) -> &mut TempDirBuilder {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets a function that generates every candidate directory name,
+    /// taking over from all the built-in naming settings.
+    ///
+    /// Each attempt calls `name_fn` once and uses the result as the whole
+    /// name, ignoring the prefix, suffix, separator, [`template`] and
+    /// random settings. As with the built-in schemes, a name that is
+    /// already taken is followed by another call, up to the
+    /// [`max_retries`] limit, so the function should not return the same
+    /// name forever. [`plan_name`] calls it too.
+    ///
+    /// Defaults to none, using the built-in schemes.
+    ///
+    /// [`template`]: struct.TempDirBuilder.html#method.template
+    /// [`max_retries`]: struct.TempDirBuilder.html#method.max_retries
+    /// [`plan_name`]: struct.TempDirBuilder.html#method.plan_name
+    ///
+    /// # Errors
+    ///
+    /// If a generated name is empty, is `.` or `..`, or contains a path
+    /// separator or a NUL byte, creation fails with
+    /// `ErrorKind::InvalidInput` without retrying.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let mut run = 0;
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .name_fn(move || {
+    ///         run += 1;
+    ///         format!("job-42-run-{}", run)
+    ///     })
+    ///     .tempdir()?;
+    /// let name = tmp_dir.path().file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with("job-42-run-"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn name_fn<F>(&mut self, name_fn: F) -> &mut TempDirBuilder
+        where F: FnMut() -> String + Send + 'static
+    {
+        self.name_fn = Some(Arc::new(Mutex::new(Box::new(name_fn))));
+        self
+    }
+
+    /// Sets whether the current process ID is included in the name.
+    ///
+    /// The ID is placed between the prefix and the generated part, as in
+    /// `prefix.1234.a1B2c3D4e5F6`, making it easy to tell which process
+    /// left a directory behind in a shared location. It is set off from
+    /// the generated part by the same [`separator`] as the prefix.
+    ///
+    /// [`separator`]: struct.TempDirBuilder.html#method.separator
+    ///
+    /// Defaults to `false`.
+    pub fn include_pid(&mut self, include_pid: bool) -> &mut TempDirBuilder {
+        self.include_pid = include_pid;
+        self
+    }
+
+    /// Sets a function to call when the directory can't be removed as
+    /// the `TempDir` is dropped.
+    ///
+    /// The destructor otherwise ignores cleanup errors, so directories
+    /// that fail to be removed leak silently. The handler is called with
+    /// the path of the directory and the error, and can be used to log
+    /// the failure or count leaks. It is not called for errors returned
+    /// from [`TempDir::close`], which the caller already sees.
+    ///
+    /// [`TempDir::close`]: struct.TempDir.html#method.close
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDir;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::builder()
+    ///     .prefix("example")
+    ///     .on_cleanup_error(|path, err| {
+    ///         eprintln!("failed to remove {}: {}", path.display(), err);
+    ///     })
+    ///     .tempdir()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_cleanup_error<F>(&mut self, handler: F) -> &mut TempDirBuilder
+        where F: Fn(&Path, &io::Error) + Send + Sync + 'static
+    {
+        self.on_cleanup_error = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets whether dropping the `TempDir` panics if the directory can't
+    /// be removed.
+    ///
+    /// This makes leaked directories impossible to miss in tests. The
+    /// panic message includes the path and the error. If the thread is
+    /// already panicking, the message is printed to standard error
+    /// instead, as a second panic would abort the process. Any handler
+    /// set with [`on_cleanup_error`] is called first.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`on_cleanup_error`]: struct.TempDirBuilder.html#method.on_cleanup_error
+    pub fn panic_on_cleanup_error(&mut self, panic_on_cleanup_error: bool)
+                                  -> &mut TempDirBuilder {
+        self.panic_on_cleanup_error = panic_on_cleanup_error;
+        self
+    }
+
+    /// Sets how the directory is removed on drop and by the `close`
+    /// methods.
+    ///
+    /// With [`CleanupMode::Empty`] only an empty directory is removed.
+    /// Anything left inside makes removal fail, which is reported like
+    /// any other cleanup error, through [`on_cleanup_error`] or
+    /// [`panic_on_cleanup_error`] on drop and as an `Err` from
+    /// [`TempDir::close`]. Code that is meant to clean up after itself
+    /// can use this to catch files it forgot about.
+    ///
+    /// Defaults to [`CleanupMode::Recursive`].
+    ///
+    /// [`CleanupMode::Empty`]: enum.CleanupMode.html#variant.Empty
+    /// [`CleanupMode::Recursive`]: enum.CleanupMode.html#variant.Recursive
+    /// [`on_cleanup_error`]: struct.TempDirBuilder.html#method.on_cleanup_error
+    /// [`panic_on_cleanup_error`]: struct.TempDirBuilder.html#method.panic_on_cleanup_error
+    /// [`TempDir::close`]: struct.TempDir.html#method.close
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::{self, File};
+    /// use tempdir::{CleanupMode, TempDir};
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDir::builder()
+    ///     .prefix("example")
+    ///     .cleanup_mode(CleanupMode::Empty)
+    ///     .tempdir()?;
+    /// let path = tmp_dir.path().to_path_buf();
+    /// File::create(path.join("forgotten.txt"))?;
+    /// assert!(tmp_dir.close().is_err());
+    ///
+    /// // The directory and the forgotten file are still there.
+    /// fs::remove_dir_all(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cleanup_mode(&mut self, cleanup_mode: CleanupMode) -> &mut TempDirBuilder {
+        self.cleanup_mode = cleanup_mode;
+        self
+    }
+
+    /// Sets the provider used to create the directory and remove it
+    /// again.
+    ///
+    /// This is meant for testing code that creates temporary
+    /// directories, by simulating the file system; see [`FsProvider`] for
+    /// which operations it covers. Clones of this builder share the same
+    /// provider, as does every `TempDir` created from it.
+    ///
+    /// Defaults to [`RealFs`].
+    ///
+    /// [`FsProvider`]: trait.FsProvider.html
+    /// [`RealFs`]: struct.RealFs.html
+    pub fn fs_provider<F: FsProvider + 'static>(&mut self, provider: F) -> &mut TempDirBuilder {
+        self.fs = Some(Arc::new(provider));
+        self
+    }
+
+    /// Sets candidate base directories for [`tempdir`] to choose from in
+    /// place of `env::temp_dir()`.
+    ///
+    /// Each call to [`tempdir`] or [`try_tempdir`] picks one of `bases`
+    /// at random and creates the directory there, spreading the load of
+    /// many temporary directories across several scratch file systems.
+    /// [`TempDir::base`] tells which one was used. Methods given an
+    /// explicit base, such as [`tempdir_in`], ignore this setting. An
+    /// empty list, the default, restores the usual base.
+    ///
+    /// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`try_tempdir`]: struct.TempDirBuilder.html#method.try_tempdir
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    /// [`TempDir::base`]: struct.TempDir.html#method.base
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("shard")
+    ///     .bases(vec![PathBuf::from("/scratch0"), PathBuf::from("/scratch1")])
+    ///     .try_next_base(true)
+    ///     .tempdir()?;
+    /// println!("created on {}", tmp_dir.base().display());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bases(&mut self, bases: Vec<PathBuf>) -> &mut TempDirBuilder {
+        self.bases = bases;
+        self
+    }
+
+    /// Sets whether a failure to create the directory in the base chosen
+    /// from [`bases`] moves on to the next one in the list.
+    ///
+    /// When enabled, the remaining bases are tried in order, wrapping
+    /// around, until one succeeds, and the error from the last is
+    /// returned if none do. Invalid name settings fail the same way in
+    /// every base, so they are reported straight away.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`bases`]: struct.TempDirBuilder.html#method.bases
+    pub fn try_next_base(&mut self, try_next_base: bool) -> &mut TempDirBuilder {
+        self.try_next_base = try_next_base;
+        self
+    }
+
+    /// Returns a [`LazyTempDir`] that creates the configured directory
+    /// inside of `env::temp_dir()`, or the base set with
+    /// [`TempDir::set_default_base`], when it is first used.
+    ///
+    /// The builder's settings are captured now; changing the builder
+    /// afterwards does not affect the returned value.
+    ///
+    /// [`LazyTempDir`]: struct.LazyTempDir.html
+    /// [`TempDir::set_default_base`]: struct.TempDir.html#method.set_default_base
+    pub fn lazy(&self) -> LazyTempDir {
+        LazyTempDir::from_builder(self.clone(), None)
+    }
+
+    /// Returns a [`LazyTempDir`] that creates the configured directory
+    /// inside of `tmpdir` when it is first used.
+    ///
+    /// A relative `tmpdir` is resolved when the directory is created.
+    ///
+    /// [`LazyTempDir`]: struct.LazyTempDir.html
+    pub fn lazy_in<P: AsRef<Path>>(&self, tmpdir: P) -> LazyTempDir {
+        LazyTempDir::from_builder(self.clone(), Some(tmpdir.as_ref().to_path_buf()))
+    }
+
+    /// Creates the configured temporary directory inside of
+    /// `env::temp_dir()`, or the base set with
+    /// [`TempDir::set_default_base`], or one of the [`bases`] if any are
+    /// set.
+    ///
+    /// [`TempDir::set_default_base`]: struct.TempDir.html#method.set_default_base
+    /// [`bases`]: struct.TempDirBuilder.html#method.bases
+    ///
+    /// # Errors
+    ///
+    /// If the directory can not be created, `Err` is returned.
+    pub fn tempdir(&self) -> io::Result<TempDir> {
+        self.try_tempdir().map_err(io::Error::from)
+    }
+
+    /// Creates the configured temporary directory inside of the
+    /// `namespace` directory of `env::temp_dir()`, or of the base set with
+    /// [`TempDir::set_default_base`], creating `namespace` first if it
+    /// does not exist.
+    ///
+    /// [`TempDir::set_default_base`]: struct.TempDir.html#method.set_default_base
+    ///
+    /// This groups all of an application's temporary directories under
+    /// one path such as `/tmp/myapp`, which makes it easy to find and
+    /// remove any that were leaked. The namespace directory is shared
+    /// and is never removed; only the randomized leaf is managed by the
+    /// returned `TempDir`. Several processes may create the same
+    /// namespace concurrently.
+    ///
+    /// # Errors
+    ///
+    /// If `namespace` is empty, absolute, or contains a `..` component,
+    /// an error with `ErrorKind::InvalidInput` is returned. Otherwise the
+    /// same errors as [`tempdir_in`] are returned.
+    ///
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::env;
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let tmp_dir = TempDirBuilder::new()
+    ///     .prefix("job")
+    ///     .tempdir_in_namespace("myapp")?;
+    /// assert_eq!(tmp_dir.base(), env::temp_dir().join("myapp"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tempdir_in_namespace<P: AsRef<Path>>(&self, namespace: P) -> io::Result<TempDir> {
+        let namespace = namespace.as_ref();
+        let valid = namespace.components().next().is_some() &&
+                    namespace.components().all(|c| {
+                        matches!(c, path::Component::Normal(_) | path::Component::CurDir)
+                    });
+        if !valid {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "namespace must be a non-empty relative path without `..`"));
+        }
+
+        let base = default_base().join(namespace);
+        if let Err(e) = fs::create_dir_all(&base) {
+            // Another process may have created it in the meantime.
+            if e.kind() != ErrorKind::AlreadyExists || !base.is_dir() {
+                return Err(e);
+            }
+        }
+        self.tempdir_in(base)
+    }
+
+    /// Creates the configured temporary directory inside of `tmpdir`.
+    ///
+    /// On Windows, if the resulting path could exceed `MAX_PATH`, `tmpdir`
+    /// is converted to an extended-length `\\?\` path first so that the
+    /// directory can still be created and removed. The returned
+    /// [`TempDir::path`] then has that form.
+    ///
+    /// [`TempDir::path`]: struct.TempDir.html#method.path
+    ///
+    /// # Errors
+    ///
+    /// If the prefix, suffix, separator or random alphabet contains a path
+    /// separator or a NUL byte, if the random alphabet is empty, or if the
+    /// name would be empty because there is no prefix, process ID or
+    /// random characters, an error with `ErrorKind::InvalidInput` is
+    /// returned. If the directory can not be created, `Err` is returned.
+    pub fn tempdir_in<P: AsRef<Path>>(&self, tmpdir: P) -> io::Result<TempDir> {
+        self.try_tempdir_in(tmpdir).map_err(io::Error::from)
+    }
+
+    /// Creates `count` directories with this configuration inside of
+    /// `tmpdir`, all or nothing.
+    ///
+    /// If one of them can't be created, the ones made before it are
+    /// removed before the error is returned, even when the
+    /// `TEMPDIR_KEEP` environment variable is set.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`tempdir_in`], for the first directory that fails.
+    /// Errors removing the ones already created are ignored.
+    ///
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    pub fn tempdir_batch_in<P: AsRef<Path>>(&self, tmpdir: P, count: usize)
+                                            -> io::Result<Vec<TempDir>> {
+        let tmpdir = tmpdir.as_ref();
+        let mut dirs = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.tempdir_in(tmpdir) {
+                Ok(dir) => dirs.push(dir),
+                Err(e) => {
+                    // `close` rather than drop, which would keep them
+                    // when `TEMPDIR_KEEP` is set.
+                    for dir in dirs {
+                        let _ = dir.close();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Creates the configured temporary directory in the first of
+    /// `candidates` that works and has at least `min_free` bytes
+    /// available.
+    ///
+    /// This is for fast scratch space with a fallback: list a `tmpfs`
+    /// mount first and a disk-backed base after it, and the directory is
+    /// created on disk only when the `tmpfs` is too full or unusable.
+    /// Candidates are tried in order. One is skipped if its free space,
+    /// as reported by [`TempDir::available_space`], is below `min_free`,
+    /// or if creating the directory there fails. With a `min_free` of
+    /// zero the free space is not checked, which also works on platforms
+    /// where it can't be queried. [`TempDir::base`] tells which candidate
+    /// was used.
+    ///
+    /// [`TempDir::available_space`]: struct.TempDir.html#method.available_space
+    /// [`TempDir::base`]: struct.TempDir.html#method.base
+    ///
+    /// # Errors
+    ///
+    /// If no candidate can be used, an error with `ErrorKind::Other` is
+    /// returned whose message lists why each one was skipped. If there
+    /// are no candidates at all, or the name settings are invalid, an
+    /// error with `ErrorKind::InvalidInput` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// # use std::io;
+    /// # fn run() -> Result<(), io::Error> {
+    /// let scratch = TempDirBuilder::new()
+    ///     .prefix("build")
+    ///     .tempdir_in_first(["/dev/shm", "/var/tmp"], 512 * 1024 * 1024)?;
+    /// println!("building in {}", scratch.path().display());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tempdir_in_first<I, P>(&self, candidates: I, min_free: u64) -> io::Result<TempDir>
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>
+    {
+        let mut skipped = Vec::new();
+        for base in candidates {
+            let base = base.as_ref();
+            if min_free > 0 {
+                match available_space(base) {
+                    Ok(free) if free < min_free => {
+                        skipped.push(format!("{}: {} bytes available, {} needed",
+                                             base.display(), free, min_free));
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        skipped.push(format!("{}: {}", base.display(), e));
+                        continue;
+                    }
+                }
+            }
+            match self.try_tempdir_in(base) {
+                Ok(dir) => return Ok(dir),
+                Err(e @ TempDirError::InvalidName(_)) => return Err(e.into()),
+                Err(e) => skipped.push(format!("{}: {}", base.display(), e)),
+            }
+        }
+
+        if skipped.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "no candidate base directories given"));
+        }
+        Err(Error::other(format!("no candidate base directory could be used ({})",
+                                 skipped.join("; "))))
+    }
+
+    /// Creates the configured temporary directory inside of
+    /// `env::temp_dir()`, or one of the [`bases`] if any are set,
+    /// returning a [`TempDirError`] on failure.
+    ///
+    /// [`bases`]: struct.TempDirBuilder.html#method.bases
+    /// [`TempDirError`]: enum.TempDirError.html
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`try_tempdir_in`] are returned.
+    ///
+    /// [`try_tempdir_in`]: struct.TempDirBuilder.html#method.try_tempdir_in
+    pub fn try_tempdir(&self) -> Result<TempDir, TempDirError> {
+        if self.bases.is_empty() {
+            return self.try_tempdir_in(default_base());
+        }
+
+        let n = self.bases.len();
+        let start = random::Source::thread().index(n);
+        let mut tried = 0;
+        loop {
+            let base = &self.bases[(start + tried) % n];
+            tried += 1;
+            match self.try_tempdir_in(base) {
+                Err(e @ TempDirError::InvalidName(_)) => return Err(e),
+                Err(_) if self.try_next_base && tried < n => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Creates the configured temporary directory inside of `tmpdir` like
+    /// [`tempdir_in`], returning a [`TempDirError`] on failure.
+    ///
+    /// [`tempdir_in`]: struct.TempDirBuilder.html#method.tempdir_in
+    /// [`TempDirError`]: enum.TempDirError.html
+    ///
+    /// # Errors
+    ///
+    /// If every name tried already exists, `TempDirError::Exhausted` is
+    /// returned. If the configured name parts are rejected by
+    /// [`tempdir_in`], `TempDirError::InvalidName` is returned. Other
+    /// failures are returned as `TempDirError::Io`.
+    pub fn try_tempdir_in<P: AsRef<Path>>(&self, tmpdir: P) -> Result<TempDir, TempDirError> {
+        self.check_name().map_err(|e| TempDirError::InvalidName(e.to_string()))?;
+
+        let storage;
+        let mut tmpdir = tmpdir.as_ref();
+        if !tmpdir.is_absolute() {
+            let cur_dir = env::current_dir()?;
+            storage = cur_dir.join(tmpdir);
+            tmpdir = &storage;
+        }
+
+        #[cfg(windows)]
+        let extended;
+        #[cfg(windows)]
+        {
+            // Leave room for the directory name, including a process ID
+            // and an attempt counter.
+            let name_len = self.prefix.len() + self.suffix.len() +
+                           self.template.as_ref().map_or(0, |t| t.len()) + self.rand_bytes + 32;
+            if tmpdir.as_os_str().len() + name_len >= WINDOWS_MAX_DIR_PATH {
+                extended = extended_length_path(tmpdir);
+                tmpdir = &extended;
+            }
+        }
+
+        if self.create_parents {
+            fs::create_dir_all(tmpdir)?;
+        }
+        if self.check_base {
+            check_base(tmpdir)?;
+        }
+        if self.require_sticky {
+            check_sticky(tmpdir)?;
+        }
+        #[cfg(target_os = "linux")]
+        let private;
+        #[cfg(target_os = "linux")]
+        {
+            if self.private {
+                private = private_parent(tmpdir, self.max_retries)?;
+                tmpdir = &private;
+            }
+        }
+
+        self.with_rng(|rng| self.create_in(tmpdir, rng))
+    }
+
+    /// Generates a directory name with the current settings and returns
+    /// it, without creating anything.
+    ///
+    /// This previews what a name from [`tempdir`] looks like, for logging
+    /// or for checking a [`template`] or [`rand_alphabet`]. The name is
+    /// drawn from the configured random number generator, so it is only
+    /// an example: a later [`tempdir`] call draws a new one. With
+    /// sequential naming the first name, ending in `0`, is returned.
+    ///
+    /// The settings are not validated, so a prefix containing a path
+    /// separator, for example, shows up in the name as given instead of
+    /// causing an error as it does when creating the directory.
+    ///
+    /// [`tempdir`]: struct.TempDirBuilder.html#method.tempdir
+    /// [`template`]: struct.TempDirBuilder.html#method.template
+    /// [`rand_alphabet`]: struct.TempDirBuilder.html#method.rand_alphabet
+    ///
+    /// # Panics
+    ///
+    /// Panics if a random alphabet has been set to an empty slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tempdir::TempDirBuilder;
+    ///
+    /// let name = TempDirBuilder::new().prefix("build").suffix(".tmp").rand_bytes(6).plan_name();
+    /// assert!(name.starts_with("build.") && name.ends_with(".tmp"));
+    /// assert_eq!(name.len(), "build..tmp".len() + 6);
+    /// ```
+    pub fn plan_name(&self) -> String {
+        self.with_rng(|rng| self.leaf_name(0, rng))
+    }
+
+    // Calls `f` with the configured random number generator, or the
+    // default one, which the builder reuses across calls.
+    fn with_rng<T, F>(&self, f: F) -> T
+        where F: FnOnce(&mut random::Source) -> T
+    {
+        #[cfg(feature = "rand")]
+        {
+            if let Some(ref rng) = self.rng {
+                // A panic while the generator was borrowed cannot leave it
+                // in a state that matters to us, so ignore poisoning.
+                let mut rng = rng.lock().unwrap_or_else(|e| e.into_inner());
+                return f(&mut random::Source::Rng(&mut **rng));
+            }
+            self.default_rng.with(f)
+        }
+        #[cfg(not(feature = "rand"))]
+        f(&mut random::Source::thread())
+    }
+
+    // Checks that the configured parts can form a single file name.
+    fn check_name(&self) -> io::Result<()> {
+        check_name_part(&self.name_prefix(), "prefix")?;
+        check_name_part(&self.suffix, "suffix")?;
+        if let Some(separator) = self.separator {
+            check_name_part(&separator.to_string(), "separator")?;
+        }
+        if let Some(ref alphabet) = self.alphabet {
+            if alphabet.is_empty() {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      "random alphabet must not be empty"));
+            }
+            check_name_part(&alphabet.iter().collect::<String>(), "random alphabet")?;
+        }
+        let no_rand = self.naming == Naming::Random && self.rand_bytes == 0 && !self.include_pid;
+        if let Some(ref template) = self.template {
+            let fixed = template.replacen(TEMPLATE_PLACEHOLDER, "", 1);
+            if fixed.contains(TEMPLATE_PLACEHOLDER) {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      "template must contain at most one `{}`"));
+            }
+            check_name_part(template, "template")?;
+            if fixed.is_empty() && no_rand {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      "an empty template requires at least one random character"));
+            }
+        } else if self.name_prefix().is_empty() && no_rand && self.name_fn.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  "an empty prefix requires at least one random character"));
+        }
+        Ok(())
+    }
+
+    fn create_in(&self, tmpdir: &Path, rng: &mut random::Source)
+                 -> Result<TempDir, TempDirError> {
+        // Without any random characters every attempt would produce the
+        // same name, so there is no point in retrying.
+        let retries = if self.naming == Naming::Random && self.rand_bytes == 0 &&
+                         self.name_fn.is_none() {
+            cmp::min(1, self.max_retries)
+        } else {
+            self.max_retries
+        };
+        let mode = if self.restrict_to_user { self.mode & 0o700 } else { self.mode };
+        #[cfg(all(windows, not(feature = "windows-acl")))]
+        {
+            if self.restrict_to_user {
+                return Err(Error::new(ErrorKind::Unsupported,
+                                      "restricting access to the current user on Windows \
+                                       requires the `windows-acl` feature").into());
+            }
+        }
+
+        let name = |attempt| {
+            let leaf = self.leaf_name(attempt, rng);
+            if self.name_fn.is_some() {
+                check_name_part(&leaf, "generated name")
+                    .map_err(|e| TempDirError::InvalidName(e.to_string()))?;
+                if leaf.is_empty() || leaf == "." || leaf == ".." {
+                    return Err(TempDirError::InvalidName(
+                        format!("`{}` is not a valid directory name", leaf)));
+                }
+            }
+            if leaf.len() > self.max_name_len {
+                return Err(TempDirError::InvalidName(format!(
+                    "directory name would be {} bytes long, more than the limit of {}",
+                    leaf.len(), self.max_name_len)));
+            }
+            Ok(leaf)
+        };
+        let create = |path: &Path| match self.fs {
+            Some(ref fs) => fs.create_dir(path, mode),
+            None => create_dir(path, mode),
+        };
+        let (path, (), attempts) =
+            create_unique(tmpdir, retries, self.collision_backoff, name, create)?;
+
+        let mut dir = TempDir::wrap(path);
+        dir.prefix = self.name_prefix().into_owned();
+        dir.attempts = attempts;
+        dir.keep_on_panic = self.keep_on_panic;
+        dir.on_cleanup_error = self.on_cleanup_error.clone();
+        dir.panic_on_cleanup_error = self.panic_on_cleanup_error;
+        dir.cleanup_mode = self.cleanup_mode;
+        dir.fs = self.fs.clone();
+        dir.max_depth = self.max_depth;
+        let mut finished = Ok(());
+        if self.setgid {
+            finished = set_setgid(dir.path());
+        }
+        if finished.is_ok() && self.restrict_to_user {
+            finished = restrict_to_user(dir.path());
+        }
+        if let Err(e) = finished {
+            // Not left to `Drop`, which would keep the directory under
+            // `TEMPDIR_KEEP` or could panic.
+            let _ = remove_with(self.fs.as_deref(), self.cleanup_mode, dir.path());
+            dir.keep = true;
+            return Err(e.into());
+        }
+        Ok(dir)
+    }
+
+    // The prefix as it goes into the name.
+    fn name_prefix(&self) -> Cow<'_, str> {
+        if !self.sanitize_prefix {
+            return Cow::Borrowed(&self.prefix);
+        }
+        let unsafe_char = |c: char| {
+            c == '/' || c == '\\' || c.is_ascii_control() || "<>:\"|?*".contains(c)
+        };
+        if self.prefix.contains(unsafe_char) {
+            Cow::Owned(self.prefix.replace(unsafe_char, "_"))
+        } else {
+            Cow::Borrowed(&self.prefix)
+        }
+    }
+
+    // The directory name to try on the given attempt.
+    fn leaf_name(&self, attempt: u32, rng: &mut random::Source) -> String {
+        if let Some(ref name_fn) = self.name_fn {
+            // As with the random number generator, a panic in an earlier
+            // call can't leave anything here inconsistent.
+            let mut name_fn = name_fn.lock().unwrap_or_else(|e| e.into_inner());
+            return (*name_fn)();
+        }
+        let mut rand = match self.naming {
+            Naming::Random => match self.alphabet {
+                Some(ref alphabet) => rng.chars_from(self.rand_bytes, alphabet),
+                None => rng.chars(self.rand_bytes),
+            },
+            Naming::Sequential => attempt.to_string(),
+        };
+        if self.include_pid {
+            rand = if rand.is_empty() {
+                process::id().to_string()
+            } else {
+                match self.separator {
+                    Some(separator) => format!("{}{}{}", process::id(), separator, rand),
+                    None => format!("{}{}", process::id(), rand),
+                }
+            };
+        }
+        match self.template {
+            Some(ref template) => template_name(template, &rand),
+            None => {
+                let prefix = self.name_prefix();
+                let mut leaf = leaf_name(&prefix, self.separator, &rand, &self.suffix);
+                if self.dot_without_prefix && prefix.is_empty() {
+                    leaf.insert(0, '.');
+                }
+                leaf
+            }
+        }
+    }
+}
+
+// The base directory used when none is given explicitly.
+pub(crate) fn default_base() -> PathBuf {
+    match DEFAULT_BASE.get() {
+        Some(base) => base.clone(),
+        None => env::temp_dir(),
+    }
+}
+
+// Removes `root` and everything below it, carrying on past failures and
+// returning each one. For `TempDir::close_verbose`.
+fn remove_tree_reporting(root: &Path, max_depth: usize) -> Vec<(PathBuf, io::Error)> {
+    let mut failures: Vec<(PathBuf, io::Error)> = Vec::new();
+    // Directories are pushed once to be emptied and again, marked `true`,
+    // to be removed after their contents, along with their depth.
+    let mut stack = vec![(root.to_path_buf(), false, 0)];
+    while let Some((dir, emptied, depth)) = stack.pop() {
+        if emptied {
+            // A directory holding an entry that could not be removed is
+            // bound to fail too; the entry is the interesting failure.
+            let blocked = failures.iter().any(|f| f.0.starts_with(&dir));
+            if let Err(e) = fs::remove_dir(&dir) {
+                if !blocked {
+                    failures.push((dir, e));
+                }
+            }
+            continue;
+        }
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                failures.push((dir, e));
+                continue;
+            }
+        };
+        stack.push((dir.clone(), true, depth));
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    failures.push((dir.clone(), e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    failures.push((path, e));
+                    continue;
+                }
+            };
+            if file_type.is_dir() {
+                if depth + 1 >= max_depth {
+                    // Anything inside would be too deep, so leave it alone.
+                    if fs::remove_dir(&path).is_err() {
+                        failures.push((path, walk::too_deep(root, max_depth)));
+                    }
+                    continue;
+                }
+                stack.push((path, false, depth + 1));
+            } else if let Err(e) = fs::remove_file(&path) {
+                // Windows removes symlinks to directories as directories.
+                if cfg!(windows) && file_type.is_symlink() && fs::remove_dir(&path).is_ok() {
+                    continue;
+                }
+                failures.push((path, e));
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(unix)]
+fn create_symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(original: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    // A relative target is resolved from the link's directory, not ours.
+    let target = match link.parent() {
+        Some(parent) => parent.join(original),
+        None => original.to_path_buf(),
+    };
+    if target.is_dir() {
+        symlink_dir(original, link)
+    } else {
+        symlink_file(original, link)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn create_symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(ErrorKind::Unsupported,
+                       "symbolic links are not supported on this platform"))
+}
+
+// Whether `KEEP_ENV_VAR` asks for directories to be kept on drop.
+fn keep_from_env() -> bool {
+    *KEEP_FROM_ENV.get_or_init(|| match env::var_os(KEEP_ENV_VAR) {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    })
+}
+
+#[cfg(windows)]
+const TMPDIR_VARS: &[&str] = &["TEMP", "TMP"];
+#[cfg(not(windows))]
+const TMPDIR_VARS: &[&str] = &["TMPDIR"];
+
+// Resolves the base directory for `TempDir::new_in_tmpdir_env`.
+fn tmpdir_from_env() -> io::Result<PathBuf> {
+    for var in TMPDIR_VARS {
+        let dir = match env::var_os(var) {
+            Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => continue,
+        };
+        if !dir.is_dir() {
+            return Err(Error::new(ErrorKind::NotFound,
+                                  format!("{} names a directory that does not exist: {}",
+                                          var, dir.display())));
+        }
+        return Ok(dir);
+    }
+    Ok(env::temp_dir())
+}
+
+/// Generates a name in the same format `TempDir` uses for new
+/// directories: `prefix` followed by a dot and `rand_len` random ASCII
+/// alphanumeric characters.
+///
+/// If `prefix` is empty, the dot is left out so the name doesn't start
+/// with one and become hidden on some systems. The characters are drawn
+/// from `rand::thread_rng()`, or from the operating system when built
+/// with the `minimal-rng` feature instead of `rand`. Nothing is created
+/// on the file system, so callers building their own temporary files
+/// must still handle the name already being taken.
+///
+/// # Examples
+///
+/// ```
+/// let name = tempdir::random_name("example", 12);
+/// assert!(name.starts_with("example."));
+/// assert_eq!(name.len(), "example.".len() + 12);
+/// ```
+pub fn random_name(prefix: &str, rand_len: usize) -> String {
+    leaf_name(prefix, Some('.'), &random::Source::thread().chars(rand_len), "")
+}
+
+// Tries up to `attempts` paths in `tmpdir`, named by `name` for each
+// attempt, until `create` makes one that did not exist yet. Returns the
+// path, what `create` returned and how many attempts it took. Only
+// collisions are retried; other errors are returned straight away. After
+// every collision but the last, sleeps for a random fraction of
+// `backoff`, drawn from the default source rather than one `name` may be
+// using so that a seeded generator still yields the same names.
+fn create_unique<T, N, C>(tmpdir: &Path, attempts: u32, backoff: Duration,
+                          mut name: N, mut create: C)
+                          -> Result<(PathBuf, T, u32), TempDirError>
+    where N: FnMut(u32) -> Result<String, TempDirError>,
+          C: FnMut(&Path) -> io::Result<T>
+{
+    for attempt in 0..attempts {
+        let path = tmpdir.join(name(attempt)?);
+        match create(&path) {
+            Ok(made) => return Ok((path, made, attempt + 1)),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {
+                if backoff > Duration::from_secs(0) && attempt + 1 < attempts {
+                    let fraction = random::Source::thread().index(1001) as u32;
+                    thread::sleep(backoff * fraction / 1000);
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(TempDirError::Exhausted { attempts })
+}
+
+// Joins the parts of a temporary file or directory name together.
+fn leaf_name(prefix: &str, separator: Option<char>, rand: &str, suffix: &str) -> String {
+    if rand.is_empty() {
+        format!("{}{}", prefix, suffix)
+    } else if !prefix.is_empty() {
+        match separator {
+            Some(separator) => format!("{}{}{}{}", prefix, separator, rand, suffix),
+            None => format!("{}{}{}", prefix, rand, suffix),
+        }
+    } else {
+        // If we're given an empty string for a prefix, then creating a
+        // directory starting with "." would lead to it being
+        // semi-invisible on some systems.
+        format!("{}{}", rand, suffix)
+    }
+}
+
+// The token in a `TempDirBuilder::template` replaced by the random part.
+const TEMPLATE_PLACEHOLDER: &str = "{}";
+
+// Fills in the directory name from a `TempDirBuilder::template`.
+fn template_name(template: &str, rand: &str) -> String {
+    if template.contains(TEMPLATE_PLACEHOLDER) {
+        template.replacen(TEMPLATE_PLACEHOLDER, rand, 1)
+    } else {
+        format!("{}{}", template, rand)
+    }
+}
+
+#[cfg(unix)]
+fn create_dir(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    fs::DirBuilder::new().mode(mode).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_dir(path: &Path, _mode: u32) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+// Explains up front why directories can't be created in `base`.
+fn check_base(base: &Path) -> io::Result<()> {
+    let metadata = match fs::metadata(base) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {
+            return Err(Error::new(ErrorKind::NotFound,
+                                  format!("base directory does not exist: {}", base.display())));
+        }
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Err(Error::new(ErrorKind::NotADirectory,
+                              format!("base is not a directory: {}", base.display())));
+    }
+    check_base_writable(base)
+}
+
+#[cfg(unix)]
+fn check_base_writable(base: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_base = CString::new(base.as_os_str().as_bytes())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    // Creating an entry needs both write and search permission.
+    if unsafe { libc::access(c_base.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Err(Error::new(ErrorKind::PermissionDenied,
+                              format!("base directory is not writable: {}", base.display())));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_base_writable(_base: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn check_sticky(base: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(base)?.permissions().mode();
+    if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+        return Err(Error::new(ErrorKind::PermissionDenied,
+                              format!("{} is world-writable but not sticky", base.display())));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_sticky(_base: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Returns this process's private parent inside `base` for
+// `TempDirBuilder::private`, creating it the first time.
+#[cfg(target_os = "linux")]
+fn private_parent(base: &Path, attempts: u32) -> Result<PathBuf, TempDirError> {
+    // The base, the process that made the parent (a forked child must
+    // not share its parent's) and the parent.
+    static PARENTS: Mutex<Vec<(PathBuf, u32, PathBuf)>> = Mutex::new(Vec::new());
+
+    let pid = process::id();
+    let mut parents = PARENTS.lock().unwrap_or_else(|e| e.into_inner());
+    parents.retain(|&(_, owner, ref parent)| owner == pid && parent.is_dir());
+    if let Some(entry) = parents.iter().find(|entry| entry.0 == base) {
+        return Ok(entry.2.clone());
+    }
+
+    let mut rng = random::Source::thread();
+    let name = |_| Ok(format!("tempdir-private.{}.{}", pid, rng.chars(8)));
+    let (parent, (), _) = create_unique(base, attempts, Duration::from_secs(0), name,
+                                        |path| create_dir(path, 0o700))?;
+    parents.push((base.to_path_buf(), pid, parent.clone()));
+    Ok(parent)
+}
+
+#[cfg(unix)]
+fn set_setgid(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode() & 0o7777;
+    permissions.set_mode(mode | 0o2000);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_setgid(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(windows, feature = "windows-acl"))]
+fn restrict_to_user(path: &Path) -> io::Result<()> {
+    acl::restrict_to_user(path)
+}
+
+// `create_in` fails before getting here.
+#[cfg(all(windows, not(feature = "windows-acl")))]
+fn restrict_to_user(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Unix handles this through the mode the directory is created with.
+#[cfg(not(windows))]
+fn restrict_to_user(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    unsafe {
+        let mut stat: libc::statvfs = mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut stat) != 0 {
+            return Err(Error::last_os_error());
+        }
+        // The field types vary between platforms.
+        #[allow(clippy::unnecessary_cast)]
+        Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+#[cfg(windows)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+    use winapi::um::winnt::ULARGE_INTEGER;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    unsafe {
+        let mut available: ULARGE_INTEGER = ::std::mem::zeroed();
+        if GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, ptr::null_mut(),
+                               ptr::null_mut()) == 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(*available.QuadPart())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(Error::new(ErrorKind::Unsupported, "available space can not be queried on this platform"))
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(fs::metadata(path)?.ino())
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> io::Result<u64> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::{GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION};
+    use winapi::um::winbase::FILE_FLAG_BACKUP_SEMANTICS;
+
+    // Directories can only be opened with backup semantics.
+    let dir = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+    unsafe {
+        let mut info: BY_HANDLE_FILE_INFORMATION = ::std::mem::zeroed();
+        if GetFileInformationByHandle(dir.as_raw_handle() as _, &mut info) == 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok((info.nFileIndexHigh as u64) << 32 | info.nFileIndexLow as u64)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_id(_path: &Path) -> io::Result<u64> {
+    Err(Error::new(ErrorKind::Unsupported, "file IDs can not be queried on this platform"))
+}
+
+// Removes the directory `path`, with everything inside it under
+// `CleanupMode::Recursive` or only if it is empty under `CleanupMode::Empty`,
+// through `fs` if there is one.
+pub(crate) fn remove_with(fs: Option<&dyn FsProvider>, mode: CleanupMode, path: &Path)
+                          -> io::Result<()> {
+    match (mode, fs) {
+        (CleanupMode::Recursive, Some(fs)) => fs.remove_dir_all(path),
+        (CleanupMode::Recursive, None) => cleanup_dir(path),
+        (CleanupMode::Empty, Some(fs)) => fs.remove_dir(path),
+        (CleanupMode::Empty, None) => fs::remove_dir(path),
+    }
+}
+
+// Removes a temporary directory and everything inside it.
+//
+// Symbolic links inside the tree are unlinked, never followed, so nothing
+// outside of `path` can be removed. On Unix `remove_dir_all` is the standard
+// library's, which checks each entry without following links and is robust
+// against entries being swapped for links mid-removal; the Windows version
+// removes reparse points without traversing them.
+#[cfg(not(windows))]
+pub(crate) fn cleanup_dir(path: &Path) -> io::Result<()> {
+    remove_dir_all(path)
+}
+
+// On Windows a directory often can't be removed right away because a virus
+// scanner or indexer briefly holds a handle to something inside it, so
+// transient failures are retried a few times before giving up.
+#[cfg(windows)]
+pub(crate) fn cleanup_dir(path: &Path) -> io::Result<()> {
+    const ATTEMPTS: u32 = 5;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_DIR_NOT_EMPTY: i32 = 145;
+
+    let mut attempt = 1;
+    loop {
+        match remove_dir_all(path) {
+            Err(ref e) if attempt < ATTEMPTS &&
+                          (e.kind() == ErrorKind::PermissionDenied ||
+                           e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) ||
+                           e.raw_os_error() == Some(ERROR_DIR_NOT_EMPTY)) => {
+                thread::sleep(Duration::from_millis(10 << attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// The longest path `CreateDirectoryW` accepts without the `\\?\` prefix:
+// `MAX_PATH` less room for an 8.3 file name.
+#[cfg(windows)]
+const WINDOWS_MAX_DIR_PATH: usize = 248;
+
+// Converts an absolute path to its extended-length form so that Windows
+// accepts it past `MAX_PATH`. Such paths are not normalized by the system,
+// so `.` and `..` components are resolved here. Paths that are already
+// verbatim, or device paths, are returned unchanged.
+#[cfg(windows)]
+fn extended_length_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let mut extended = match components.next() {
+        Some(Component::Prefix(prefix)) => {
+            let mut s = OsString::new();
+            match prefix.kind() {
+                Prefix::Disk(_) => {
+                    s.push(r"\\?\");
+                    s.push(prefix.as_os_str());
+                }
+                Prefix::UNC(server, share) => {
+                    s.push(r"\\?\UNC\");
+                    s.push(server);
+                    s.push(r"\");
+                    s.push(share);
+                }
+                _ => return path.to_path_buf(),
+            }
+            PathBuf::from(s)
+        }
+        _ => return path.to_path_buf(),
+    };
+    for component in components {
+        match component {
+            Component::RootDir => extended.push(r"\"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                extended.pop();
+            }
+            Component::Normal(part) => extended.push(part),
+            Component::Prefix(_) => unreachable!(),
+        }
+    }
+    extended
+}
+
+// Copies the tree under `src` into the existing directory `dest`. Symlinks
+// are not traversed; see `TempDir::copy_from`.
+fn copy_tree(src: &Path, dest: &Path, max_depth: usize) -> io::Result<()> {
+    walk::walk(src, max_depth, |path, metadata| {
+        // Every path the walk yields lives under `src`.
+        let target = dest.join(path.strip_prefix(src).unwrap());
+        if metadata.is_dir() {
+            fs::create_dir_all(&target)
+        } else if metadata.is_file() {
+            fs::copy(path, &target).map(|_| ())
+        } else {
+            match fs::metadata(path) {
+                Ok(ref linked) if linked.is_file() => fs::copy(path, &target).map(|_| ()),
+                _ => Ok(()),
+            }
+        }
+    })
+}
+
+// Copies `src` to a new, synced file in the directory of `dest`, for
+// `TempDir::commit_file` to rename into place, and returns its path.
+fn copy_next_to(src: &Path, dest: &Path) -> io::Result<PathBuf> {
+    let name = dest.file_name().ok_or_else(|| {
+        Error::new(ErrorKind::InvalidInput,
+                   format!("{} does not name a file", dest.display()))
+    })?;
+    let dir = match dest.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut rng = random::Source::thread();
+    let mut source = fs::File::open(src)?;
+    loop {
+        let staged = dir.join(format!(".{}.{}.tmp", name.to_string_lossy(), rng.chars(8)));
+        let mut file = match fs::OpenOptions::new().write(true).create_new(true).open(&staged) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        let copied = io::copy(&mut source, &mut file).and_then(|_| file.sync_all());
+        return match copied {
+            Ok(()) => Ok(staged),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&staged);
+                Err(e)
+            }
+        };
+    }
+}
+
+// Resolves `.` and `..` components without touching the file system. A
+// `..` at the root stays there, as the file system treats it.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            path::Component::CurDir => {}
+            path::Component::ParentDir => {
+                match normalized.components().next_back() {
+                    Some(path::Component::Normal(_)) => {
+                        normalized.pop();
+                    }
+                    Some(path::Component::RootDir) | Some(path::Component::Prefix(_)) => {}
+                    _ => normalized.push(".."),
+                }
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+    normalized
+}
+
+// Whether `s` contains anything `Path::join` would treat as a separator,
+// which would make the leaf name span more than one path component.
+fn has_separator(s: &str) -> bool {
+    s.chars().any(|c| c == '/' || path::is_separator(c))
+}
+
+// Rejects a prefix or suffix that could not be part of a single file name,
+// so that it can't create nested directories or escape `tmpdir` via `..`.
+fn check_name_part(part: &str, what: &str) -> io::Result<()> {
+    if has_separator(part) || part.contains('\0') {
+        return Err(Error::new(ErrorKind::InvalidInput,
+                              format!("{} must not contain a path separator or NUL byte",
+                                      what)));
+    }
+    Ok(())
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.path()
+    }
+}
 
 impl fmt::Debug for TempDir {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("TempDir")
             .field("path", &self.path())
+            .field("cleanup", &!self.keep)
+            .field("keep_on_panic", &self.keep_on_panic)
+            .field("created", &self.created_by_us)
+            .field("nested", &self.nested)
+            .field("cleanup_mode", &self.cleanup_mode)
             .finish()
     }
 }
 
+/// Two `TempDir`s are equal if they manage the same path.
+///
+/// Normally every `TempDir` manages a distinct directory. Two equal
+/// handles can only come about by adopting the same path twice, for
+/// instance with [`TempDir::from_existing_path`], in which case both
+/// will try to delete the directory when dropped.
+///
+/// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+impl PartialEq for TempDir {
+    fn eq(&self, other: &TempDir) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for TempDir {}
+
+impl Hash for TempDir {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+/// `TempDir`s are ordered by the paths they manage, so a collection of
+/// them can be sorted for deterministic iteration.
+///
+/// A live handle always has a path. Should one without a path ever be
+/// compared, it orders before every handle that has one, consistent with
+/// `PartialEq`.
+impl PartialOrd for TempDir {
+    fn partial_cmp(&self, other: &TempDir) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TempDir {
+    fn cmp(&self, other: &TempDir) -> cmp::Ordering {
+        self.path.cmp(&other.path)
+    }
+}
+
+/// Adopts an existing directory, like [`TempDir::from_existing_path`].
+///
+/// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+impl TryFrom<PathBuf> for TempDir {
+    type Error = io::Error;
+
+    fn try_from(path: PathBuf) -> io::Result<TempDir> {
+        TempDir::from_existing_path(path)
+    }
+}
+
+/// Adopts an existing directory, like [`TempDir::from_existing_path`].
+///
+/// [`TempDir::from_existing_path`]: struct.TempDir.html#method.from_existing_path
+impl<'a> TryFrom<&'a Path> for TempDir {
+    type Error = io::Error;
+
+    fn try_from(path: &'a Path) -> io::Result<TempDir> {
+        TempDir::from_existing_path(path.to_path_buf())
+    }
+}
+
+impl fmt::Display for TempDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.path().display().fmt(f)
+    }
+}
+
 impl Drop for TempDir {
     fn drop(&mut self) {
-        // Path is `None` if `close()` or `into_path()` has been called.
-        if let Some(ref p) = self.path {
-            let _ =  remove_dir_all(p);
+        if self.keep {
+            return;
+        }
+
+        // Path is `None` if `close()` or `into_path()` has been called. It is
+        // taken here too, so nothing can see it once removal has started.
+        if let Some(p) = self.path.take() {
+            if self.keep_on_panic && thread::panicking() {
+                eprintln!("tempdir: keeping {} after panic", p.display());
+                return;
+            }
+            if keep_from_env() {
+                eprintln!("tempdir: keeping {} ({} is set)", p.display(), KEEP_ENV_VAR);
+                return;
+            }
+            let e = match self.remove_all(&p) {
+                Ok(()) => return,
+                Err(ref e) if self.nested && e.kind() == ErrorKind::NotFound => return,
+                Err(e) => e,
+            };
+            if let Some(ref handler) = self.on_cleanup_error {
+                handler(&p, &e);
+            }
+            if self.panic_on_cleanup_error {
+                if thread::panicking() {
+                    eprintln!("tempdir: failed to remove {}: {}", p.display(), e);
+                } else {
+                    panic!("failed to remove temporary directory {}: {}", p.display(), e);
+                }
+            }
         }
     }
 }
@@ -0,0 +1,100 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use {cleanup_dir, create_dir};
+
+/// The file system operations a [`TempDir`] uses to create and remove
+/// its directory.
+///
+/// Code that creates temporary directories can be tested against a fake
+/// implementation installed with [`TempDirBuilder::fs_provider`], for
+/// instance one that reports `ErrorKind::AlreadyExists` for every name to
+/// exercise the exhaustion path, without touching the disk. Normal use
+/// goes through [`RealFs`].
+///
+/// Only creating the directory and removing it on drop or [`close`] go
+/// through the provider, with [`remove_dir`] used instead of
+/// [`remove_dir_all`] under [`CleanupMode::Empty`]. Other operations,
+/// such as creating missing parents, setting the setgid bit or the
+/// helpers on `TempDir` that read and write the directory's contents,
+/// always use the real file system.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`TempDirBuilder::fs_provider`]: struct.TempDirBuilder.html#method.fs_provider
+/// [`RealFs`]: struct.RealFs.html
+/// [`close`]: struct.TempDir.html#method.close
+/// [`remove_dir`]: trait.FsProvider.html#method.remove_dir
+/// [`remove_dir_all`]: trait.FsProvider.html#tymethod.remove_dir_all
+/// [`CleanupMode::Empty`]: enum.CleanupMode.html#variant.Empty
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, ErrorKind};
+/// use std::path::Path;
+/// use tempdir::{FsProvider, TempDirBuilder};
+///
+/// struct Crowded;
+///
+/// impl FsProvider for Crowded {
+///     fn create_dir(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+///         Err(io::Error::new(ErrorKind::AlreadyExists, "taken"))
+///     }
+///
+///     fn remove_dir_all(&self, _path: &Path) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let err = TempDirBuilder::new()
+///     .max_retries(10)
+///     .fs_provider(Crowded)
+///     .tempdir()
+///     .unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+/// ```
+pub trait FsProvider: Send + Sync {
+    /// Creates the directory `path`, with permissions `mode` on Unix.
+    ///
+    /// This must fail with `ErrorKind::AlreadyExists` if `path` exists,
+    /// so that another name is tried.
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Removes the directory `path` and everything inside it.
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes the directory `path`, which must be empty.
+    ///
+    /// The default implementation calls `fs::remove_dir` on the real
+    /// file system.
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+}
+
+/// The [`FsProvider`] that operates on the real file system.
+///
+/// [`FsProvider`]: trait.FsProvider.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealFs;
+
+impl FsProvider for RealFs {
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        create_dir(path, mode)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        cleanup_dir(path)
+    }
+}
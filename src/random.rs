@@ -0,0 +1,194 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The source of randomness for generated names.
+//!
+//! With the default `rand` feature, names are drawn from `rand`'s
+//! generators. Without it, the `minimal-rng` feature reads bytes straight
+//! from the operating system with `getrandom`. Both sample uniformly from
+//! the same alphabet, so the names they produce are equally hard to guess.
+
+#[cfg(not(feature = "rand"))]
+use std::cell::RefCell;
+#[cfg(not(feature = "rand"))]
+use std::marker::PhantomData;
+#[cfg(not(feature = "rand"))]
+use std::process;
+
+#[cfg(feature = "rand")]
+use std::process;
+#[cfg(feature = "rand")]
+use std::sync::Mutex;
+
+#[cfg(feature = "rand")]
+use rand::{self, Isaac64Rng, Rng};
+
+// The characters `Rng::gen_ascii_chars` produces.
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                              abcdefghijklmnopqrstuvwxyz\
+                              0123456789";
+
+pub enum Source<'a> {
+    #[cfg(feature = "rand")]
+    Rng(&'a mut dyn Rng),
+    #[cfg(feature = "rand")]
+    Thread(rand::ThreadRng),
+    #[cfg(not(feature = "rand"))]
+    Os(PhantomData<&'a mut ()>),
+}
+
+/// A generator kept by a `TempDirBuilder` so that repeated creations
+/// draw from it instead of fetching `rand::thread_rng()` for each name.
+///
+/// It is an ISAAC generator like the one behind `thread_rng`, seeded
+/// from `thread_rng`, so the names are as hard to guess as before.
+/// Seeding takes a few microseconds, so the first name still comes from
+/// `thread_rng` and the generator is only set up once the builder is
+/// used again; a builder made for a single directory, as in
+/// `TempDir::new`, pays nothing extra. A forked child starts over;
+/// otherwise parent and child would draw the same names. Clones start
+/// out empty rather than sharing the generator, so clones used on
+/// different threads don't contend for it.
+#[cfg(feature = "rand")]
+#[derive(Default)]
+pub struct Cached(Mutex<Option<(u32, Option<Box<Isaac64Rng>>)>>);
+
+#[cfg(feature = "rand")]
+impl Clone for Cached {
+    fn clone(&self) -> Cached {
+        Cached::default()
+    }
+}
+
+#[cfg(feature = "rand")]
+impl Cached {
+    /// Calls `f` with the cached generator, or with `thread_rng` the
+    /// first time.
+    pub fn with<T, F>(&self, f: F) -> T
+        where F: FnOnce(&mut Source) -> T
+    {
+        // Nothing `f` does can leave the generator in a state that
+        // matters, so ignore poisoning.
+        let mut cached = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let pid = process::id();
+        match *cached {
+            Some((owner, Some(ref mut rng))) if owner == pid => f(&mut Source::Rng(&mut **rng)),
+            Some((owner, None)) if owner == pid => {
+                let mut rng = Box::new(rand::thread_rng().gen::<Isaac64Rng>());
+                let result = f(&mut Source::Rng(&mut *rng));
+                *cached = Some((pid, Some(rng)));
+                result
+            }
+            _ => {
+                *cached = Some((pid, None));
+                f(&mut Source::thread())
+            }
+        }
+    }
+}
+
+// Random bytes read from the OS but not used yet, the position of the
+// next unused byte, and the ID of the process that read them. Keeping them
+// per thread rather than per `Source` means creating many directories in
+// a row costs one `getrandom` call per buffer, not one per directory, much
+// like `rand::thread_rng` is reused. A forked child inherits the buffer,
+// so it is thrown away when the process ID changes; otherwise parent and
+// child would draw the same names.
+#[cfg(not(feature = "rand"))]
+thread_local! {
+    static OS_BYTES: RefCell<OsBytes> = const { RefCell::new(([0; 256], 256, 0)) };
+}
+
+#[cfg(not(feature = "rand"))]
+type OsBytes = ([u8; 256], usize, u32);
+
+impl<'a> Source<'a> {
+    /// The default source: `rand::thread_rng()`, or the operating system
+    /// when built without `rand`.
+    #[cfg(feature = "rand")]
+    pub fn thread() -> Source<'static> {
+        Source::Thread(rand::thread_rng())
+    }
+
+    /// The default source: `rand::thread_rng()`, or the operating system
+    /// when built without `rand`.
+    #[cfg(not(feature = "rand"))]
+    pub fn thread() -> Source<'static> {
+        // Checked once per source rather than for every draw, since
+        // getting the process ID is a system call.
+        OS_BYTES.with(|bytes| {
+            let (ref buf, ref mut pos, pid) = *bytes.borrow_mut();
+            if pid != process::id() {
+                *pos = buf.len();
+            }
+        });
+        Source::Os(PhantomData)
+    }
+
+    /// Draws `len` random ASCII alphanumeric characters.
+    pub fn chars(&mut self, len: usize) -> String {
+        (0..len).map(|_| ALPHANUMERIC[self.index(ALPHANUMERIC.len())] as char).collect()
+    }
+
+    /// Draws `len` characters uniformly from `alphabet`, which must not be
+    /// empty.
+    pub fn chars_from(&mut self, len: usize, alphabet: &[char]) -> String {
+        (0..len).map(|_| alphabet[self.index(alphabet.len())]).collect()
+    }
+
+    /// Returns a uniformly distributed index below `n`, which must not be
+    /// zero.
+    #[cfg(feature = "rand")]
+    pub fn index(&mut self, n: usize) -> usize {
+        // Calling through `&mut &mut dyn Rng` reaches the `Sized` methods.
+        match *self {
+            Source::Rng(ref mut rng) => (&mut &mut **rng).gen_range(0, n),
+            Source::Thread(ref mut rng) => rng.gen_range(0, n),
+        }
+    }
+
+    /// Returns a uniformly distributed index below `n`, which must not be
+    /// zero.
+    #[cfg(not(feature = "rand"))]
+    pub fn index(&mut self, n: usize) -> usize {
+        assert!(n > 0 && n as u64 <= u32::MAX as u64);
+        let n = n as u32;
+        // Reject the top values that would make some indexes more likely
+        // than others.
+        let limit = (u32::MAX / n) * n;
+        loop {
+            let v = self.next_u32();
+            if v < limit {
+                return (v % n) as usize;
+            }
+        }
+    }
+
+    #[cfg(not(feature = "rand"))]
+    fn next_u32(&mut self) -> u32 {
+        use getrandom;
+
+        match *self {
+            Source::Os(_) => OS_BYTES.with(|bytes| {
+                let (ref mut buf, ref mut pos, ref mut pid) = *bytes.borrow_mut();
+                if *pos + 4 > buf.len() {
+                    // `rand::thread_rng` panics if the OS can't provide
+                    // entropy as well; there is no sensible fallback.
+                    getrandom::getrandom(buf).expect("failed to read random bytes from the OS");
+                    *pos = 0;
+                    *pid = process::id();
+                }
+                let b = &buf[*pos..*pos + 4];
+                *pos += 4;
+                (b[0] as u32) | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+            }),
+        }
+    }
+}
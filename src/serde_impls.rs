@@ -0,0 +1,37 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `Serialize` and `Deserialize` for `TempDir`, enabled by the `serde`
+//! feature.
+//!
+//! A `TempDir` is serialized as its path. Deserializing one goes through
+//! `TempDir::from_existing_path`, so the resulting handle takes ownership
+//! of the directory and will delete it when dropped, just like the
+//! original would have.
+
+use std::path::PathBuf;
+
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+use TempDir;
+
+impl Serialize for TempDir {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TempDir {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TempDir, D::Error> {
+        let path = PathBuf::deserialize(deserializer)?;
+        TempDir::from_existing_path(path).map_err(D::Error::custom)
+    }
+}
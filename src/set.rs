@@ -0,0 +1,85 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+use std::path::PathBuf;
+
+use TempDir;
+
+/// A collection of [`TempDir`]s that are closed together.
+///
+/// Test harnesses that create many temporary directories can collect
+/// them here and call [`close_all`] once at the end, getting back every
+/// directory that could not be removed instead of stopping at the first
+/// error. Directories still in the set when it is dropped are removed
+/// like any other `TempDir`, ignoring errors.
+///
+/// [`TempDir`]: struct.TempDir.html
+/// [`close_all`]: struct.TempDirSet.html#method.close_all
+///
+/// # Examples
+///
+/// ```
+/// use tempdir::{TempDir, TempDirSet};
+///
+/// # use std::io;
+/// # fn run() -> Result<(), io::Error> {
+/// let mut dirs = TempDirSet::new();
+/// for _ in 0..4 {
+///     dirs.push(TempDir::new("worker")?);
+/// }
+///
+/// for (path, err) in dirs.close_all() {
+///     eprintln!("failed to remove {}: {}", path.display(), err);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct TempDirSet(Vec<TempDir>);
+
+impl TempDirSet {
+    /// Creates an empty set.
+    pub fn new() -> TempDirSet {
+        TempDirSet(Vec::new())
+    }
+
+    /// Adds `dir` to the set, which takes over closing it.
+    pub fn push(&mut self, dir: TempDir) {
+        self.0.push(dir);
+    }
+
+    /// Returns the number of directories in the set.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the set holds no directories.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Closes every directory in the set, returning the path and error of
+    /// each one that could not be removed.
+    ///
+    /// All directories are attempted, in the order they were added, even
+    /// if some of them fail. An empty result means everything was
+    /// removed.
+    pub fn close_all(self) -> Vec<(PathBuf, io::Error)> {
+        let mut failures = Vec::new();
+        for dir in self.0 {
+            let path = dir.path().to_path_buf();
+            if let Err(e) = dir.close() {
+                failures.push((path, e));
+            }
+        }
+        failures
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recursive traversal of a directory tree.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Calls `f` with the path and metadata of every entry below `root`,
+/// not including `root` itself.
+///
+/// Symbolic links are reported as links and never followed, so the walk
+/// cannot escape the tree or loop. Directories are visited before their
+/// contents. The traversal keeps its own stack rather than recursing, so
+/// deep trees cannot overflow the call stack. Entries directly inside
+/// `root` are at depth 1; finding one deeper than `max_depth` stops the
+/// walk with the error from `too_deep`.
+pub fn walk<F>(root: &Path, max_depth: usize, mut f: F) -> io::Result<()>
+    where F: FnMut(&Path, &Metadata) -> io::Result<()>
+{
+    let mut stack: Vec<(PathBuf, usize)> = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = stack.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if depth + 1 > max_depth {
+                return Err(too_deep(root, max_depth));
+            }
+            // `DirEntry::metadata` does not traverse symlinks.
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+            f(&path, &metadata)?;
+            if metadata.is_dir() {
+                stack.push((path, depth + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The error for a tree below `root` nested more than `max_depth` deep.
+pub fn too_deep(root: &Path, max_depth: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,
+                   format!("{} is nested more than {} directories deep",
+                           root.display(), max_depth))
+}
@@ -0,0 +1,42 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The default base is process-wide, so this lives in its own test binary
+// to keep it from redirecting the directories of the other tests.
+
+extern crate tempdir;
+
+use std::io::ErrorKind;
+
+use tempdir::{TempDir, TempDirBuilder, TempFile};
+
+macro_rules! t {
+    ($e:expr) => (match $e { Ok(n) => n, Err(e) => panic!("error: {}", e) })
+}
+
+#[test]
+fn main() {
+    let base = t!(TempDir::new_in(std::env::temp_dir(), "test_default_base"));
+    t!(TempDir::set_default_base(base.path()));
+
+    let dir = t!(TempDir::new("test_default_base"));
+    assert_eq!(dir.base(), base.path());
+    let dir = t!(TempDirBuilder::new().prefix("test_default_base").tempdir());
+    assert_eq!(dir.base(), base.path());
+    let dir = t!(TempDirBuilder::new().tempdir_in_namespace("ns"));
+    assert_eq!(dir.base(), base.path().join("ns"));
+    let file = t!(TempFile::new("test_default_base"));
+    assert_eq!(file.path().parent(), Some(base.path()));
+
+    let err = TempDir::set_default_base(std::env::temp_dir()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    let dir = t!(TempDir::new("test_default_base"));
+    assert_eq!(dir.base(), base.path());
+}
@@ -0,0 +1,49 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `TEMPDIR_KEEP` is read once per process, so this lives in its own test
+// binary to keep it from leaving behind the directories of the other tests.
+
+extern crate tempdir;
+
+use std::env;
+use std::fs;
+
+use tempdir::{TempDir, KEEP_ENV_VAR};
+
+macro_rules! t {
+    ($e:expr) => (match $e { Ok(n) => n, Err(e) => panic!("error: {}", e) })
+}
+
+#[test]
+fn main() {
+    assert_eq!(KEEP_ENV_VAR, "TEMPDIR_KEEP");
+    env::set_var(KEEP_ENV_VAR, "1");
+
+    let dir = t!(TempDir::new("test_keep_env"));
+    let path = dir.path().to_path_buf();
+    drop(dir);
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+
+    // The variable is only read once.
+    env::remove_var(KEEP_ENV_VAR);
+    let dir = t!(TempDir::new("test_keep_env"));
+    let path = dir.path().to_path_buf();
+    drop(dir);
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+
+    // Closing explicitly still removes the directory.
+    let dir = t!(TempDir::new("test_keep_env"));
+    let path = dir.path().to_path_buf();
+    t!(dir.close());
+    assert!(!path.exists());
+}
@@ -8,35 +8,36 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 extern crate tempdir;
 
+use std::convert::TryFrom;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::mpsc::channel;
 use std::thread;
 
-use tempdir::TempDir;
+#[cfg(feature = "rand")]
+use rand::{SeedableRng, XorShiftRng};
+
+use tempdir::{CleanupMode, FsProvider, LazyTempDir, Naming, TempDir, TempDirBuilder, TempDirError,
+              TempDirSet, TempFile};
 
 macro_rules! t {
     ($e:expr) => (match $e { Ok(n) => n, Err(e) => panic!("error: {}", e) })
 }
 
-trait PathExt {
-    fn exists(&self) -> bool;
-    fn is_dir(&self) -> bool;
-}
-
-impl PathExt for Path {
-    fn exists(&self) -> bool { fs::metadata(self).is_ok() }
-    fn is_dir(&self) -> bool {
-        fs::metadata(self).map(|m| m.is_dir()).unwrap_or(false)
-    }
-}
-
 fn test_tempdir() {
     let path = {
-        let p = t!(TempDir::new_in(&Path::new("."), "foobar"));
+        let p = t!(TempDir::new_in(Path::new("."), "foobar"));
         let p = p.path();
         assert!(p.to_str().unwrap().contains("foobar"));
         p.to_path_buf()
@@ -138,17 +139,17 @@ fn recursive_mkdir_rel() {
     let cwd = env::current_dir().unwrap();
     println!("recursive_mkdir_rel: Making: {} in cwd {} [{}]", path.display(),
            cwd.display(), path.exists());
-    t!(fs::create_dir(&path));
+    t!(fs::create_dir(path));
     assert!(path.is_dir());
-    t!(fs::create_dir_all(&path));
+    t!(fs::create_dir_all(path));
     assert!(path.is_dir());
 }
 
 fn recursive_mkdir_dot() {
     let dot = Path::new(".");
-    t!(fs::create_dir_all(&dot));
+    t!(fs::create_dir_all(dot));
     let dotdot = Path::new("..");
-    t!(fs::create_dir_all(&dotdot));
+    t!(fs::create_dir_all(dotdot));
 }
 
 fn recursive_mkdir_rel_2() {
@@ -156,14 +157,14 @@ fn recursive_mkdir_rel_2() {
     let cwd = env::current_dir().unwrap();
     println!("recursive_mkdir_rel_2: Making: {} in cwd {} [{}]", path.display(),
              cwd.display(), path.exists());
-    t!(fs::create_dir_all(&path));
+    t!(fs::create_dir_all(path));
     assert!(path.is_dir());
     assert!(path.parent().unwrap().is_dir());
     let path2 = Path::new("quux/blat");
     println!("recursive_mkdir_rel_2: Making: {} in cwd {}", path2.display(),
              cwd.display());
     t!(fs::create_dir("quux"));
-    t!(fs::create_dir_all(&path2));
+    t!(fs::create_dir_all(path2));
     assert!(path2.is_dir());
     assert!(path2.parent().unwrap().is_dir());
 }
@@ -176,9 +177,9 @@ pub fn test_remove_dir_all_ok() {
 
     println!("making {}", root.display());
     t!(fs::create_dir(&root));
-    t!(fs::create_dir(&root.join("foo")));
-    t!(fs::create_dir(&root.join("foo").join("bar")));
-    t!(fs::create_dir(&root.join("foo").join("bar").join("blat")));
+    t!(fs::create_dir(root.join("foo")));
+    t!(fs::create_dir(root.join("foo").join("bar")));
+    t!(fs::create_dir(root.join("foo").join("bar").join("blat")));
     t!(fs::remove_dir_all(&root));
     assert!(!root.exists());
     assert!(!root.join("bar").exists());
@@ -213,6 +214,2154 @@ pub fn pass_as_asref_path() {
         let path = path.as_ref();
         assert!(path.exists());
     }
+
+    // Standard library functions bounded by `AsRef<Path>` accept the
+    // handle directly.
+    assert!(t!(fs::metadata(&tempdir)).is_dir());
+    assert_eq!(t!(fs::read_dir(&tempdir)).count(), 0);
+}
+
+pub fn test_builder() {
+    let mut builder = TempDirBuilder::new();
+    builder.prefix("builder").suffix("-work").rand_bytes(4);
+
+    let a = t!(builder.tempdir_in(Path::new(".")));
+    let b = t!(builder.clone().tempdir_in(Path::new(".")));
+    assert!(a.path() != b.path());
+    for dir in &[&a, &b] {
+        let name = dir.path().file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("builder."));
+        assert!(name.ends_with("-work"));
+        assert_eq!(name.len(), "builder.".len() + 4 + "-work".len());
+        assert!(dir.path().is_dir());
+    }
+}
+
+pub fn test_suffix() {
+    let dir = t!(TempDir::builder().suffix(".tmp").tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap().to_owned();
+    assert!(!name.starts_with('.'));
+    assert!(name.ends_with(".tmp"));
+    assert!(!name.contains(".."));
+
+    let dir = t!(TempDir::builder().prefix("p").suffix(".tmp").tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap().to_owned();
+    assert!(name.starts_with("p."));
+    assert!(name.ends_with(".tmp"));
+    assert!(!name.contains(".."));
+
+    let err = TempDir::builder().suffix("a/b").tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_rand_bytes() {
+    let dir = t!(TempDir::builder().prefix("short").rand_bytes(3).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap().to_owned();
+    assert_eq!(name.len(), "short.".len() + 3);
+
+    let dir = t!(TempDir::builder().prefix("exact").rand_bytes(0).tempdir_in("."));
+    assert_eq!(dir.path().file_name().unwrap(), "exact");
+    let err = TempDir::builder().prefix("exact").rand_bytes(0).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+    let err = TempDir::builder().rand_bytes(0).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_max_retries() {
+    let mut builder = TempDir::builder();
+    builder.prefix("bounded").rand_bytes(0).max_retries(5);
+    let _dir = t!(builder.tempdir_in("."));
+
+    builder.rand_bytes(1);
+    let err = builder.max_retries(0).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert!(err.to_string().contains("0 attempts"));
+}
+
+#[cfg(feature = "rand")]
+pub fn test_custom_rng() {
+    use std::time::Duration;
+
+    t!(fs::create_dir("a"));
+    t!(fs::create_dir("b"));
+    let seed = [0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb];
+
+    let mut a = TempDir::builder();
+    a.prefix("seeded").rng(XorShiftRng::from_seed(seed));
+    let mut b = TempDir::builder();
+    b.prefix("seeded").rng(XorShiftRng::from_seed(seed));
+
+    for _ in 0..3 {
+        let da = t!(a.tempdir_in("a"));
+        let db = t!(b.tempdir_in("b"));
+        assert_eq!(da.path().file_name(), db.path().file_name());
+    }
+
+    // Backing off after a collision leaves the seeded sequence alone.
+    let mut plan = TempDir::builder();
+    plan.prefix("seeded").rng(XorShiftRng::from_seed(seed));
+    let (first, second) = (plan.plan_name(), plan.plan_name());
+    t!(fs::create_dir("c"));
+    t!(fs::create_dir(Path::new("c").join(&first)));
+    let mut c = TempDir::builder();
+    c.prefix("seeded").rng(XorShiftRng::from_seed(seed));
+    c.collision_backoff(Duration::from_millis(1));
+    let dc = t!(c.tempdir_in("c"));
+    assert_eq!(dc.attempts(), 2);
+    assert_eq!(dc.path().file_name().unwrap(), &second[..]);
+}
+
+#[cfg(not(feature = "rand"))]
+pub fn test_custom_rng() {}
+
+pub fn test_reused_builder() {
+    use std::collections::HashSet;
+
+    // The generator a builder keeps after its first directory carries on
+    // drawing fresh names rather than repeating earlier ones.
+    let mut builder = TempDir::builder();
+    builder.prefix("reused");
+    let dirs: Vec<_> = (0..100).map(|_| t!(builder.tempdir_in("."))).collect();
+    let names: HashSet<_> = dirs.iter().map(|d| d.path().to_path_buf()).collect();
+    assert_eq!(names.len(), dirs.len());
+}
+
+pub fn test_deref_path() {
+    let tmp = t!(TempDir::new("test"));
+    let file = tmp.join("file.txt");
+    assert_eq!(file, tmp.path().join("file.txt"));
+    assert!(tmp.is_dir());
+
+    fn takes_path(path: &Path) -> bool {
+        path.exists()
+    }
+    assert!(takes_path(&tmp));
+}
+
+#[allow(deprecated)]
+pub fn test_child() {
+    let tmp = t!(TempDir::new("test"));
+    let child = tmp.child("a").join("b");
+    assert_eq!(child, tmp.path().join("a/b"));
+    assert!(!child.exists());
+    assert_eq!(tmp.child(""), tmp.path().join(""));
+}
+
+pub fn test_disable_cleanup() {
+    let mut tmp = t!(TempDir::new("test_disable_cleanup"));
+    let path = tmp.path().to_path_buf();
+    tmp.disable_cleanup();
+    drop(tmp);
+    assert!(path.exists());
+    t!(fs::remove_dir(&path));
+
+    let mut tmp = t!(TempDir::new("test_disable_cleanup"));
+    let path = tmp.path().to_path_buf();
+    tmp.disable_cleanup();
+    t!(tmp.close());
+    assert!(path.exists());
+    t!(fs::remove_dir(&path));
+}
+
+pub fn test_keep_on_panic() {
+    let (tx, rx) = channel();
+    let _ = thread::spawn(move || {
+        let tmp = t!(TempDir::builder().prefix("keep").keep_on_panic(true).tempdir());
+        tx.send(tmp.path().to_path_buf()).unwrap();
+        panic!("panic to unwind past `tmp`");
+    }).join();
+    let path = rx.recv().unwrap();
+    assert!(path.exists());
+    t!(fs::remove_dir(&path));
+
+    // Without a panic the directory is still removed.
+    let tmp = t!(TempDir::builder().prefix("keep").keep_on_panic(true).tempdir());
+    let path = tmp.path().to_path_buf();
+    drop(tmp);
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+pub fn test_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = t!(TempDir::new("test_mode"));
+    let mode = t!(fs::metadata(tmp.path())).permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    let tmp = t!(TempDir::builder().prefix("test_mode").mode(0o711).tempdir());
+    let mode = t!(fs::metadata(tmp.path())).permissions().mode();
+    assert_eq!(mode & 0o700, 0o700);
+    assert_eq!(mode & 0o777 & !0o711, 0);
+}
+
+#[cfg(not(unix))]
+pub fn test_mode() {}
+
+pub fn test_from_existing_path() {
+    let path = t!(TempDir::new("test_from_existing_path")).into_path();
+    assert!(path.exists());
+    let tmp = t!(TempDir::from_existing_path(path.clone()));
+    assert_eq!(tmp.path(), &*path);
+    drop(tmp);
+    assert!(!path.exists());
+
+    t!(fs::create_dir("relative"));
+    let tmp = t!(TempDir::from_existing_path("relative".into()));
+    assert!(tmp.path().is_absolute());
+    drop(tmp);
+    assert!(!Path::new("relative").exists());
+
+    let err = TempDir::from_existing_path("missing".into()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    t!(fs::File::create("file"));
+    let err = TempDir::from_existing_path("file".into()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+    assert!(Path::new("file").exists());
+}
+
+pub fn test_display() {
+    let tmp = t!(TempDir::new("test_display"));
+    assert_eq!(tmp.to_string(), tmp.path().display().to_string());
+    assert_eq!(format!("{:>200}", tmp), format!("{:>200}", tmp.path().display()));
+}
+
+pub fn test_create_parents() {
+    let base = Path::new("missing/base");
+    let err = TempDir::builder().prefix("parents").tempdir_in(base).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let tmp = t!(TempDir::builder().prefix("parents").create_parents(true).tempdir_in(base));
+    assert!(tmp.path().is_dir());
+    assert_eq!(tmp.path().parent().unwrap(), t!(env::current_dir()).join(base));
+    drop(tmp);
+    assert!(base.is_dir());
+}
+
+pub fn test_tempfile() {
+    let path = {
+        let mut f = t!(TempFile::new_in(".", "foobar"));
+        let name = f.path().file_name().unwrap().to_str().unwrap().to_owned();
+        assert!(name.starts_with("foobar."));
+        assert!(f.path().is_file());
+
+        t!(f.as_file_mut().write_all(b"hello"));
+        t!(f.as_file_mut().seek(SeekFrom::Start(0)));
+        let mut contents = String::new();
+        t!(f.as_file_mut().read_to_string(&mut contents));
+        assert_eq!(contents, "hello");
+        f.path().to_path_buf()
+    };
+    assert!(!path.exists());
+
+    let f = t!(TempFile::new("test_tempfile"));
+    let path = f.path().to_path_buf();
+    t!(f.close());
+    assert!(!path.exists());
+}
+
+pub fn test_close_path() {
+    let tmp = t!(TempDir::new("test_close_path"));
+    let expected = tmp.path().to_path_buf();
+    let removed = t!(tmp.close_path());
+    assert_eq!(removed, expected);
+    assert!(!removed.exists());
+
+    let tmp = t!(TempDir::new("test_close_path"));
+    t!(fs::remove_dir(tmp.path()));
+    assert!(tmp.close_path().is_err());
+}
+
+pub fn test_is_empty() {
+    let tmp = t!(TempDir::new("test_is_empty"));
+    assert!(t!(tmp.is_empty()));
+    t!(fs::create_dir(tmp.path().join("sub")));
+    assert!(!t!(tmp.is_empty()));
+
+    t!(fs::remove_dir_all(tmp.path()));
+    assert_eq!(tmp.is_empty().unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+pub fn test_size() {
+    let tmp = t!(TempDir::new("test_size"));
+    assert_eq!(t!(tmp.size()), 0);
+
+    t!(fs::create_dir_all(tmp.path().join("a/b")));
+    t!(t!(fs::File::create(tmp.path().join("top"))).write_all(&[0; 10]));
+    t!(t!(fs::File::create(tmp.path().join("a/b/nested"))).write_all(&[0; 32]));
+    assert_eq!(t!(tmp.size()), 42);
+}
+
+pub fn test_entry_count() {
+    let tmp = t!(TempDir::new("test_entry_count"));
+    assert_eq!(t!(tmp.entry_count()), 0);
+
+    t!(fs::create_dir_all(tmp.path().join("a/b/c")));
+    t!(fs::File::create(tmp.path().join("a/b/file")));
+    t!(fs::File::create(tmp.path().join("top")));
+    assert_eq!(t!(tmp.entry_count()), 5);
+
+    t!(fs::remove_dir_all(tmp.path()));
+    assert!(tmp.entry_count().is_err());
+}
+
+#[cfg(unix)]
+pub fn test_cleanup_keeps_symlink_targets() {
+    use std::os::unix::fs::symlink;
+
+    let outside = t!(TempDir::new("test_symlink_outside"));
+    t!(fs::File::create(outside.path().join("precious")));
+
+    let tmp = t!(TempDir::new("test_symlink"));
+    t!(fs::create_dir(tmp.path().join("sub")));
+    t!(symlink(outside.path(), tmp.path().join("link")));
+    t!(symlink(outside.path(), tmp.path().join("sub/link")));
+    t!(symlink(outside.path().join("precious"), tmp.path().join("file_link")));
+    let path = tmp.path().to_path_buf();
+    t!(tmp.close());
+
+    assert!(!path.exists());
+    assert!(outside.path().is_dir());
+    assert!(outside.path().join("precious").exists());
+}
+
+#[cfg(not(unix))]
+pub fn test_cleanup_keeps_symlink_targets() {}
+
+pub fn test_new_named_in() {
+    let path = {
+        let tmp = t!(TempDir::new_named_in(".", "cache"));
+        assert_eq!(tmp.path(), t!(env::current_dir()).join("cache"));
+        assert!(tmp.path().is_dir());
+
+        let err = TempDir::new_named_in(".", "cache").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        tmp.path().to_path_buf()
+    };
+    assert!(!path.exists());
+
+    for name in &["", ".", "..", "a/b"] {
+        let err = TempDir::new_named_in(".", name).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
+
+pub fn test_prefix_and_base() {
+    t!(fs::create_dir("base"));
+    let tmp = t!(TempDir::new_in("base", "foobar"));
+    assert_eq!(tmp.prefix(), "foobar");
+    assert_eq!(tmp.base(), t!(env::current_dir()).join("base"));
+
+    let adopted = t!(TempDir::from_existing_path(tmp.into_path()));
+    assert_eq!(adopted.prefix(), "");
+}
+
+#[cfg(feature = "serde")]
+pub fn test_deserialize() {
+    use serde::de::value::{Error, StrDeserializer};
+    use serde::de::{Deserialize, IntoDeserializer};
+
+    let path = t!(TempDir::new("test_deserialize")).into_path();
+    let de: StrDeserializer<Error> = path.to_str().unwrap().into_deserializer();
+    let tmp = t!(TempDir::deserialize(de));
+    assert_eq!(tmp.path(), &*path);
+    drop(tmp);
+    assert!(!path.exists());
+
+    let de: StrDeserializer<Error> = "missing".into_deserializer();
+    assert!(TempDir::deserialize(de).is_err());
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn test_deserialize() {}
+
+pub fn test_sequential_naming() {
+    let mut builder = TempDir::builder();
+    builder.prefix("seq").naming(Naming::Sequential);
+    let first = t!(builder.tempdir_in("."));
+    let second = t!(builder.tempdir_in("."));
+    assert_eq!(first.path().file_name().unwrap(), "seq.0");
+    assert_eq!(second.path().file_name().unwrap(), "seq.1");
+    drop(first);
+    let third = t!(builder.tempdir_in("."));
+    assert_eq!(third.path().file_name().unwrap(), "seq.0");
+
+    let err = builder.max_retries(2).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+}
+
+pub fn test_include_pid() {
+    let pid = process::id();
+    let mut builder = TempDir::builder();
+    builder.prefix("pid").suffix(".tmp").rand_bytes(4).include_pid(true);
+    let tmp = t!(builder.tempdir_in("."));
+    let name = tmp.path().file_name().unwrap().to_str().unwrap().to_owned();
+    assert!(name.starts_with(&format!("pid.{}.", pid)));
+    assert!(name.ends_with(".tmp"));
+    assert!(tmp.path().to_str().unwrap().contains(&pid.to_string()));
+
+    let tmp = t!(TempDir::builder().rand_bytes(0).include_pid(true).tempdir_in("."));
+    assert_eq!(tmp.path().file_name().unwrap().to_str().unwrap(), pid.to_string());
+}
+
+pub fn test_invalid_prefix() {
+    let mut prefixes = vec!["foo/bar", "../escape", "nul\0byte"];
+    if cfg!(windows) {
+        prefixes.push("foo\\bar");
+    }
+    for prefix in prefixes {
+        let err = TempDir::new_in(".", prefix).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let err = TempFile::new_in(".", prefix).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    assert!(!Path::new("foo").exists());
+
+    let err = TempDir::builder().suffix("nul\0byte").tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_enter() {
+    let before = t!(env::current_dir());
+    let tmp = t!(TempDir::new("test_enter"));
+    {
+        let guard = t!(tmp.enter());
+        assert_eq!(guard.previous(), &*before);
+        assert_eq!(t!(fs::canonicalize(t!(env::current_dir()))),
+                   t!(fs::canonicalize(tmp.path())));
+        t!(fs::File::create("marker"));
+    }
+    assert_eq!(t!(env::current_dir()), before);
+    assert!(tmp.path().join("marker").exists());
+}
+
+pub fn test_copy_from() {
+    let src = t!(TempDir::new("test_copy_from_src"));
+    t!(fs::create_dir_all(src.path().join("a/b")));
+    t!(fs::create_dir(src.path().join("empty")));
+    t!(t!(fs::File::create(src.path().join("top"))).write_all(b"top"));
+    t!(t!(fs::File::create(src.path().join("a/b/nested"))).write_all(b"nested"));
+
+    let dest = t!(TempDir::new("test_copy_from_dest"));
+    t!(dest.copy_from(src.path()));
+    assert!(dest.path().join("empty").is_dir());
+    let mut contents = String::new();
+    t!(t!(fs::File::open(dest.path().join("a/b/nested"))).read_to_string(&mut contents));
+    assert_eq!(contents, "nested");
+    assert_eq!(t!(dest.size()), t!(src.size()));
+
+    let err = dest.copy_from(src.path().join("top")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+}
+
+#[cfg(unix)]
+pub fn test_copy_from_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let src = t!(TempDir::new("test_copy_from_src"));
+    t!(t!(fs::File::create(src.path().join("file"))).write_all(b"data"));
+    t!(symlink(src.path().join("file"), src.path().join("file_link")));
+    t!(symlink(src.path(), src.path().join("loop")));
+    t!(symlink(src.path().join("missing"), src.path().join("dangling")));
+
+    let dest = t!(TempDir::new("test_copy_from_dest"));
+    t!(dest.copy_from(src.path()));
+    let link = t!(fs::symlink_metadata(dest.path().join("file_link")));
+    assert!(link.is_file());
+    assert_eq!(link.len(), 4);
+    assert!(!dest.path().join("loop").exists());
+    assert!(!dest.path().join("dangling").exists());
+}
+
+#[cfg(not(unix))]
+pub fn test_copy_from_symlinks() {}
+
+pub fn test_eq_hash() {
+    use std::collections::HashSet;
+
+    let a = t!(TempDir::new("test_eq_hash"));
+    let b = t!(TempDir::new("test_eq_hash"));
+    assert!(a != b);
+
+    let mut alias = t!(TempDir::from_existing_path(a.path().to_path_buf()));
+    alias.disable_cleanup();
+    assert!(a == alias);
+
+    let mut set = HashSet::new();
+    assert!(set.insert(&a));
+    assert!(set.insert(&b));
+    assert!(!set.insert(&alias));
+    assert_eq!(set.len(), 2);
+}
+
+pub fn test_new_in_tmpdir_env() {
+    let var = if cfg!(windows) { "TEMP" } else { "TMPDIR" };
+    let saved = env::var_os(var);
+
+    let base = t!(env::current_dir()).join("envbase");
+    t!(fs::create_dir(&base));
+    env::set_var(var, &base);
+    let result = TempDir::new_in_tmpdir_env("env");
+    env::set_var(var, base.join("missing"));
+    let missing = TempDir::new_in_tmpdir_env("env");
+
+    match saved {
+        Some(saved) => env::set_var(var, saved),
+        None => env::remove_var(var),
+    }
+    let tmp = t!(result);
+    assert_eq!(tmp.base(), &*base);
+    assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(feature = "tokio")]
+pub fn test_close_async() {
+    let rt = t!(tokio::runtime::Builder::new_current_thread().build());
+
+    let tmp = t!(TempDir::new("test_close_async"));
+    t!(fs::create_dir_all(tmp.path().join("a/b")));
+    let path = tmp.path().to_path_buf();
+    t!(rt.block_on(tmp.close_async()));
+    assert!(!path.exists());
+
+    let tmp = t!(TempDir::new("test_close_async"));
+    t!(fs::remove_dir(tmp.path()));
+    assert!(rt.block_on(tmp.close_async()).is_err());
+
+    // A future dropped before its first poll still removes the directory.
+    let tmp = t!(TempDir::new("test_close_async"));
+    t!(fs::create_dir_all(tmp.path().join("a/b")));
+    let path = tmp.path().to_path_buf();
+    drop(tmp.close_async());
+    assert!(!path.exists());
+}
+
+#[cfg(not(feature = "tokio"))]
+pub fn test_close_async() {}
+
+pub fn test_on_cleanup_error() {
+    use std::sync::{Arc, Mutex};
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let sink = failures.clone();
+    let mut builder = TempDir::builder();
+    builder.prefix("test_on_cleanup_error").on_cleanup_error(move |path, err| {
+        sink.lock().unwrap().push((path.to_path_buf(), err.kind()));
+    });
+
+    let tmp = t!(builder.tempdir());
+    drop(tmp);
+    assert!(failures.lock().unwrap().is_empty());
+
+    let tmp = t!(builder.tempdir());
+    let path = tmp.path().to_path_buf();
+    t!(fs::remove_dir(&path));
+    drop(tmp);
+    assert_eq!(*failures.lock().unwrap(), vec![(path, ErrorKind::NotFound)]);
+}
+
+pub fn test_panic_on_cleanup_error() {
+    let mut builder = TempDir::builder();
+    builder.prefix("test_panic_on_cleanup_error").panic_on_cleanup_error(true);
+
+    let tmp = t!(builder.tempdir());
+    drop(tmp);
+
+    let tmp = t!(builder.tempdir());
+    let r = thread::spawn(move || {
+        t!(fs::remove_dir(tmp.path()));
+    }).join();
+    let msg = r.unwrap_err().downcast::<String>().unwrap();
+    assert!(msg.contains("failed to remove temporary directory"));
+
+    // Like `dont_double_panic`, but with the option enabled.
+    let tmp = t!(builder.tempdir());
+    let r = thread::spawn(move || {
+        t!(fs::remove_dir(tmp.path()));
+        panic!("first panic");
+    }).join();
+    assert_eq!(*r.unwrap_err().downcast::<&str>().unwrap(), "first panic");
+}
+
+pub fn test_random_name() {
+    let name = tempdir::random_name("foobar", 8);
+    assert!(name.starts_with("foobar."));
+    assert_eq!(name.len(), "foobar.".len() + 8);
+    assert!(name["foobar.".len()..].chars().all(|c| c.is_ascii_alphanumeric()));
+
+    let name = tempdir::random_name("", 8);
+    assert_eq!(name.len(), 8);
+    assert!(!name.starts_with('.'));
+}
+
+pub fn test_new_child() {
+    let parent = t!(TempDir::new("test_new_child"));
+    let child = t!(parent.new_child("child"));
+    assert_eq!(child.base(), parent.path());
+    assert!(child.file_name().unwrap().to_str().unwrap().starts_with("child."));
+    let path = child.path().to_path_buf();
+    drop(child);
+    assert!(!path.exists());
+
+    // A child outliving its parent finds its directory gone on drop.
+    let child = t!(parent.new_child("child"));
+    let path = child.path().to_path_buf();
+    t!(parent.close());
+    assert!(!path.exists());
+    drop(child);
+}
+
+pub fn test_close_if_empty() {
+    let tmpdir = t!(TempDir::new("test_close_if_empty"));
+    let path = tmpdir.path().to_path_buf();
+    t!(tmpdir.close_if_empty());
+    assert!(!path.exists());
+
+    let tmpdir = t!(TempDir::new("test_close_if_empty"));
+    let path = tmpdir.path().to_path_buf();
+    t!(fs::File::create(path.join("stray")));
+    let err = tmpdir.close_if_empty().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DirectoryNotEmpty);
+    // The leftovers stay for inspection.
+    assert!(path.join("stray").exists());
+    t!(fs::remove_dir_all(&path));
+}
+
+pub fn test_tempdir_in_namespace() {
+    let namespace = format!("test_tempdir_in_namespace.{}", process::id());
+    let base = env::temp_dir().join(&namespace);
+    {
+        let first = t!(TempDirBuilder::new().prefix("a").tempdir_in_namespace(&namespace));
+        let second = t!(TempDirBuilder::new().prefix("b").tempdir_in_namespace(&namespace));
+        assert_eq!(first.base(), &*base);
+        assert_eq!(second.base(), &*base);
+    }
+    assert!(base.is_dir());
+    t!(fs::remove_dir(&base));
+
+    for bad in &["", "/abs", "../up", "a/../b"] {
+        let err = TempDirBuilder::new().prefix("a").tempdir_in_namespace(bad).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
+
+pub fn test_into_path_synced() {
+    let tmpdir = t!(TempDir::new("test_into_path_synced"));
+    let expected = tmpdir.path().to_path_buf();
+    let path = t!(tmpdir.into_path_synced());
+    assert_eq!(path, expected);
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+}
+
+pub fn test_ord() {
+    let mut dirs = Vec::new();
+    for name in &["c", "a", "b"] {
+        dirs.push(t!(TempDir::new_named_in(".", name)));
+    }
+    dirs.sort();
+    let names: Vec<_> = dirs.iter()
+        .map(|d| d.path().file_name().unwrap().to_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(names, ["a", "b", "c"]);
+    assert!(dirs[0] < dirs[1]);
+}
+
+#[cfg(windows)]
+pub fn test_long_path() {
+    let mut base = t!(env::current_dir());
+    for _ in 0..6 {
+        base.push("a".repeat(50));
+    }
+    let tmpdir = t!(TempDirBuilder::new().prefix("long").create_parents(true).tempdir_in(&base));
+    assert!(tmpdir.path().as_os_str().len() > 260);
+    assert!(tmpdir.path().is_dir());
+    t!(fs::write(tmpdir.path().join("file"), b"long"));
+    let path = tmpdir.path().to_path_buf();
+    t!(tmpdir.close());
+    assert!(!path.exists());
+}
+
+#[cfg(not(windows))]
+pub fn test_long_path() {}
+
+pub fn test_rand_alphabet() {
+    let alphabet = ['x', 'y', '\u{e9}'];
+    let mut builder = TempDirBuilder::new();
+    builder.prefix("alphabet").rand_bytes(16).rand_alphabet(&alphabet);
+    for _ in 0..8 {
+        let dir = t!(builder.tempdir_in("."));
+        let name = dir.path().file_name().unwrap().to_str().unwrap();
+        let rand = &name["alphabet.".len()..];
+        assert_eq!(rand.chars().count(), 16);
+        assert!(rand.chars().all(|c| alphabet.contains(&c)));
+    }
+
+    for bad in &[&[][..], &['a', '/'][..], &['\0'][..]] {
+        let err = TempDirBuilder::new().rand_alphabet(bad).tempdir_in(".").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
+
+pub fn test_attempts() {
+    let dir = t!(TempDir::new("test_attempts"));
+    assert_eq!(dir.attempts(), 1);
+
+    let mut builder = TempDirBuilder::new();
+    builder.prefix("test_attempts").naming(Naming::Sequential);
+    let first = t!(builder.tempdir_in("."));
+    let second = t!(builder.tempdir_in("."));
+    let third = t!(builder.tempdir_in("."));
+    assert_eq!(first.attempts(), 1);
+    assert_eq!(second.attempts(), 2);
+    assert_eq!(third.attempts(), 3);
+
+    let path = dir.into_path();
+    let adopted = t!(TempDir::from_existing_path(path));
+    assert_eq!(adopted.attempts(), 0);
+}
+
+pub fn test_persist_to() {
+    let tmpdir = t!(TempDir::new("test_persist_to"));
+    let src = tmpdir.path().to_path_buf();
+    t!(fs::create_dir(src.join("sub")));
+    t!(fs::write(src.join("sub").join("file"), b"persisted"));
+
+    let dest = t!(env::current_dir()).join("persisted");
+    t!(tmpdir.persist_to(&dest));
+    assert!(!src.exists());
+    assert_eq!(t!(fs::read(dest.join("sub").join("file"))), b"persisted");
+
+    // A failed move keeps the original directory and its contents.
+    let tmpdir = t!(TempDir::new("test_persist_to"));
+    let src = tmpdir.path().to_path_buf();
+    t!(fs::write(src.join("file"), b"kept"));
+    assert!(tmpdir.persist_to(dest.join("missing").join("dest")).is_err());
+    assert_eq!(t!(fs::read(src.join("file"))), b"kept");
+    t!(fs::remove_dir_all(&src));
+}
+
+pub fn test_separator() {
+    let dir = t!(TempDir::builder().prefix("sep").separator(None).rand_bytes(6).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("sep"));
+    assert!(!name.contains('.'));
+    assert_eq!(name.len(), "sep".len() + 6);
+
+    let dir = t!(TempDir::builder().prefix("sep").separator(Some('_')).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("sep_"));
+
+    let dir = t!(TempDir::builder().separator(Some('_')).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(!name.starts_with('_'));
+
+    let err = TempDir::builder().prefix("sep").separator(Some('/')).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_separator_with_pid() {
+    let pid = process::id();
+    let dir = t!(TempDir::builder().prefix("sep").separator(Some('-')).include_pid(true)
+                     .rand_bytes(6).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with(&format!("sep-{}-", pid)));
+    assert!(!name.contains('.'));
+    assert_eq!(name.len(), format!("sep-{}-", pid).len() + 6);
+
+    let dir = t!(TempDir::builder().prefix("sep").separator(None).include_pid(true)
+                     .rand_bytes(6).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with(&format!("sep{}", pid)));
+    assert_eq!(name.len(), format!("sep{}", pid).len() + 6);
+}
+
+pub fn test_try_from() {
+    let path = t!(TempDir::new("test_try_from")).into_path();
+    let tmp = t!(TempDir::try_from(path.as_path()));
+    assert_eq!(tmp.path(), &*path);
+    drop(tmp);
+    assert!(!path.exists());
+
+    let path = t!(TempDir::new("test_try_from")).into_path();
+    let tmp = t!(TempDir::try_from(path.clone()));
+    drop(tmp);
+    assert!(!path.exists());
+
+    t!(fs::File::create("file"));
+    let err = TempDir::try_from(Path::new("file")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+    let err = TempDir::try_from(PathBuf::from("file")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+    assert!(Path::new("file").exists());
+}
+
+pub fn test_clear() {
+    let tmpdir = t!(TempDir::new("test_clear"));
+    t!(fs::write(tmpdir.path().join("file"), b"data"));
+    t!(fs::create_dir_all(tmpdir.path().join("a").join("b")));
+    t!(fs::write(tmpdir.path().join("a").join("b").join("file"), b"data"));
+    t!(tmpdir.clear());
+    assert!(tmpdir.path().is_dir());
+    assert!(t!(tmpdir.is_empty()));
+
+    // Clearing an empty directory is fine too.
+    t!(tmpdir.clear());
+    assert!(t!(tmpdir.is_empty()));
+}
+
+#[cfg(unix)]
+pub fn test_clear_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let outside = t!(TempDir::new("test_clear_symlinks"));
+    t!(fs::write(outside.path().join("keep"), b"data"));
+    let tmpdir = t!(TempDir::new("test_clear_symlinks"));
+    t!(symlink(outside.path(), tmpdir.path().join("link")));
+    t!(tmpdir.clear());
+    assert!(t!(tmpdir.is_empty()));
+    assert!(outside.path().join("keep").exists());
+}
+
+#[cfg(not(unix))]
+pub fn test_clear_symlinks() {}
+
+pub fn test_typed_errors() {
+    let dir = t!(TempDir::try_new_in(".", "test_typed_errors"));
+    assert!(dir.path().is_dir());
+
+    let mut builder = TempDir::builder();
+    builder.prefix("bounded").naming(Naming::Sequential).max_retries(1);
+    let _first = t!(builder.try_tempdir_in("."));
+    match builder.try_tempdir_in(".") {
+        Err(TempDirError::Exhausted { attempts }) => assert_eq!(attempts, 1),
+        other => panic!("expected exhaustion, got {:?}", other),
+    }
+    let err = std::io::Error::from(builder.try_tempdir_in(".").unwrap_err());
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert!(err.to_string().contains("1 attempts"));
+
+    match TempDir::try_new_in(".", "a/b") {
+        Err(TempDirError::InvalidName(_)) => {}
+        other => panic!("expected an invalid name, got {:?}", other),
+    }
+    match TempDir::try_new_in("missing", "test_typed_errors") {
+        Err(TempDirError::Io(ref e)) => assert_eq!(e.kind(), ErrorKind::NotFound),
+        other => panic!("expected an I/O error, got {:?}", other),
+    }
+}
+
+pub fn test_read_dir() {
+    let tmpdir = t!(TempDir::new("test_read_dir"));
+    assert_eq!(t!(tmpdir.read_dir()).count(), 0);
+    t!(fs::File::create(tmpdir.path().join("a")));
+    t!(fs::create_dir(tmpdir.path().join("b")));
+    t!(fs::File::create(tmpdir.path().join("b").join("nested")));
+    let mut names: Vec<_> = t!(tmpdir.read_dir())
+        .map(|e| t!(e).file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a", "b"]);
+
+    let path = tmpdir.path().to_path_buf();
+    t!(fs::remove_dir_all(&path));
+    assert_eq!(tmpdir.read_dir().unwrap_err().kind(), ErrorKind::NotFound);
+    t!(fs::create_dir(&path));
+}
+
+#[cfg(unix)]
+pub fn test_setgid() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = t!(TempDir::builder().prefix("test_setgid").mode(0o750).setgid(true).tempdir());
+    let mode = t!(fs::metadata(tmp.path())).permissions().mode();
+    assert_eq!(mode & 0o2000, 0o2000);
+    assert_eq!(mode & 0o700, 0o700);
+    assert_eq!(mode & 0o777 & !0o750, 0);
+
+    let tmp = t!(TempDir::builder().prefix("test_setgid").tempdir());
+    let mode = t!(fs::metadata(tmp.path())).permissions().mode();
+    assert_eq!(mode & 0o2000, 0);
+}
+
+#[cfg(not(unix))]
+pub fn test_setgid() {}
+
+pub fn test_available_space() {
+    let tmpdir = t!(TempDir::new("test_available_space"));
+    let before = t!(tmpdir.available_space());
+    assert!(before > 0);
+
+    let path = tmpdir.path().to_path_buf();
+    t!(fs::remove_dir(&path));
+    assert_eq!(tmpdir.available_space().unwrap_err().kind(), ErrorKind::NotFound);
+    t!(fs::create_dir(&path));
+}
+
+pub fn test_tempdir_set() {
+    let mut set = TempDirSet::new();
+    assert!(set.is_empty());
+    let mut paths = Vec::new();
+    for _ in 0..3 {
+        let dir = t!(TempDir::new_in(".", "test_tempdir_set"));
+        paths.push(dir.path().to_path_buf());
+        set.push(dir);
+    }
+    assert_eq!(set.len(), 3);
+
+    // Removing one directory behind the set's back makes closing it fail
+    // without stopping the others from being closed.
+    t!(fs::remove_dir(&paths[1]));
+    let failures = set.close_all();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, paths[1]);
+    assert_eq!(failures[0].1.kind(), ErrorKind::NotFound);
+    assert!(paths.iter().all(|p| !p.exists()));
+
+    let mut set = TempDirSet::new();
+    let dir = t!(TempDir::new_in(".", "test_tempdir_set"));
+    let path = dir.path().to_path_buf();
+    set.push(dir);
+    drop(set);
+    assert!(!path.exists());
+}
+
+pub fn test_dot_without_prefix() {
+    let dir = t!(TempDir::builder().rand_bytes(8).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(!name.starts_with('.'));
+    assert_eq!(name.len(), 8);
+
+    let dir = t!(TempDir::builder().rand_bytes(8).dot_without_prefix(true).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with('.'));
+    assert!(!name.starts_with(".."));
+    assert_eq!(name.len(), 1 + 8);
+
+    // A prefix takes precedence.
+    let dir = t!(TempDir::builder().prefix("p").dot_without_prefix(true).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("p."));
+}
+
+pub fn test_fs_provider() {
+    use std::sync::{Arc, Mutex};
+
+    // Claims the first `collisions` names are taken and records what it
+    // was asked to do instead of touching the disk.
+    #[derive(Clone, Default)]
+    struct FakeFs {
+        collisions: Arc<Mutex<u32>>,
+        created: Arc<Mutex<Vec<PathBuf>>>,
+        removed: Arc<Mutex<Vec<PathBuf>>>,
+    }
+
+    impl FsProvider for FakeFs {
+        fn create_dir(&self, path: &Path, _mode: u32) -> std::io::Result<()> {
+            let mut collisions = self.collisions.lock().unwrap();
+            if *collisions > 0 {
+                *collisions -= 1;
+                return Err(std::io::Error::new(ErrorKind::AlreadyExists, "taken"));
+            }
+            self.created.lock().unwrap().push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+            self.removed.lock().unwrap().push(path.to_path_buf());
+            Ok(())
+        }
+    }
+
+    let fake = FakeFs::default();
+    *fake.collisions.lock().unwrap() = 5;
+    let dir = t!(TempDir::builder().prefix("fake").fs_provider(fake.clone()).tempdir_in("."));
+    assert_eq!(dir.attempts(), 6);
+    assert!(!dir.path().exists());
+    let path = dir.path().to_path_buf();
+    assert_eq!(fake.created.lock().unwrap().len(), 1);
+    assert_eq!(fake.created.lock().unwrap()[0], path);
+    drop(dir);
+    assert_eq!(fake.removed.lock().unwrap().len(), 1);
+    assert_eq!(fake.removed.lock().unwrap()[0], path);
+
+    let dir = t!(TempDir::builder().prefix("fake").fs_provider(fake.clone()).tempdir_in("."));
+    let path = dir.path().to_path_buf();
+    t!(dir.close());
+    assert_eq!(fake.removed.lock().unwrap().last(), Some(&path));
+
+    *fake.collisions.lock().unwrap() = 3;
+    let err = TempDir::builder().max_retries(3).fs_provider(fake.clone()).tempdir_in(".");
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::AlreadyExists);
+}
+
+pub fn test_create_file() {
+    let tmpdir = t!(TempDir::new("test_create_file"));
+    let path = t!(tmpdir.create_file("marker"));
+    assert_eq!(path, tmpdir.path().join("marker"));
+    assert_eq!(t!(fs::metadata(&path)).len(), 0);
+
+    let path = t!(tmpdir.create_file(Path::new("a").join("b").join("nested")));
+    assert!(path.is_file());
+    assert!(path.starts_with(tmpdir.path()));
+
+    t!(fs::write(&path, b"data"));
+    t!(tmpdir.create_file(Path::new("a").join("b").join("nested")));
+    assert_eq!(t!(fs::metadata(&path)).len(), 0);
+
+    let outside = t!(env::current_dir()).join("outside");
+    for bad in &[Path::new("../escape"), Path::new("a/../../escape"), &outside] {
+        let err = tmpdir.create_file(bad).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    assert!(!Path::new("outside").exists());
+}
+
+pub fn test_try_child() {
+    let tmpdir = t!(TempDir::new("test_try_child"));
+    assert_eq!(t!(tmpdir.try_child("a")), tmpdir.path().join("a"));
+    assert_eq!(t!(tmpdir.try_child("./a/./b")), tmpdir.path().join("a").join("b"));
+    assert_eq!(t!(tmpdir.try_child("a/../b")), tmpdir.path().join("b"));
+    assert_eq!(t!(tmpdir.try_child("a/..")), tmpdir.path());
+    assert_eq!(t!(tmpdir.try_child("")), tmpdir.path());
+
+    let outside = t!(env::current_dir()).join("outside");
+    for bad in &[Path::new(".."), Path::new("a/../.."), Path::new("a/../../b"), &outside] {
+        let err = tmpdir.try_child(bad).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    // `create_file` goes through the same check.
+    let path = t!(tmpdir.create_file("a/../inside"));
+    assert_eq!(path, tmpdir.path().join("inside"));
+    assert!(tmpdir.create_file("a/../../outside").is_err());
+    assert!(!Path::new("outside").exists());
+}
+
+pub fn test_close_owned() {
+    let tmpdir = t!(TempDir::new("test_close_owned"));
+    let path = tmpdir.path().to_path_buf();
+    t!(tmpdir.close_owned());
+    assert!(!path.exists());
+
+    let path = t!(TempDir::new_named_in(".", "named")).into_path();
+    let named = t!(TempDir::from_existing_path(path.clone()));
+    t!(named.close_owned());
+    assert!(path.is_dir());
+
+    let adopted = t!(TempDir::from_existing_path(path.clone()));
+    t!(adopted.close());
+    assert!(!path.exists());
+}
+
+pub fn test_path_str() {
+    let tmpdir = t!(TempDir::new("test_path_str"));
+    assert_eq!(tmpdir.path_str(), tmpdir.path().to_str());
+    assert!(tmpdir.path_str().unwrap().contains("test_path_str"));
+}
+
+#[cfg(unix)]
+pub fn test_path_str_non_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let base = Path::new(OsStr::from_bytes(b"non-utf8-\xff"));
+    if fs::create_dir(base).is_err() {
+        // Some file systems only accept UTF-8 names.
+        return;
+    }
+    let tmpdir = t!(TempDir::new_in(base, "test_path_str"));
+    assert_eq!(tmpdir.path_str(), None);
+}
+
+#[cfg(not(unix))]
+pub fn test_path_str_non_utf8() {}
+
+#[cfg(unix)]
+pub fn test_require_sticky() {
+    use std::os::unix::fs::PermissionsExt;
+
+    t!(fs::create_dir("shared"));
+    t!(fs::set_permissions("shared", fs::Permissions::from_mode(0o777)));
+    let err = TempDir::builder().require_sticky(true).tempdir_in("shared").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    // Off by default.
+    let _dir = t!(TempDir::builder().tempdir_in("shared"));
+
+    t!(fs::set_permissions("shared", fs::Permissions::from_mode(0o1777)));
+    let _dir = t!(TempDir::builder().require_sticky(true).tempdir_in("shared"));
+
+    t!(fs::set_permissions("shared", fs::Permissions::from_mode(0o755)));
+    let _dir = t!(TempDir::builder().require_sticky(true).tempdir_in("shared"));
+}
+
+#[cfg(not(unix))]
+pub fn test_require_sticky() {}
+
+#[cfg(unix)]
+pub fn test_chown() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmpdir = t!(TempDir::new("test_chown"));
+    let meta = t!(fs::metadata(tmpdir.path()));
+
+    // Setting the current owner and group is always allowed.
+    t!(tmpdir.chown(Some(meta.uid()), Some(meta.gid())));
+    t!(tmpdir.chown(None, None));
+    let after = t!(fs::metadata(tmpdir.path()));
+    assert_eq!((after.uid(), after.gid()), (meta.uid(), meta.gid()));
+
+    if meta.uid() != 0 {
+        let err = tmpdir.chown(Some(0), None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn test_chown() {}
+
+pub fn test_check_base() {
+    let mut builder = TempDir::builder();
+    builder.check_base(true);
+
+    let err = builder.tempdir_in("missing").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("base directory does not exist"));
+
+    t!(fs::File::create("file"));
+    let err = builder.tempdir_in("file").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+    assert!(err.to_string().contains("base is not a directory"));
+
+    let _dir = t!(builder.tempdir_in("."));
+
+    // Missing parents are created before the check.
+    let _dir = t!(builder.create_parents(true).tempdir_in("missing"));
+}
+
+pub fn test_canonical_path() {
+    let tmpdir = t!(TempDir::new("test_canonical_path"));
+    let canonical = t!(tmpdir.canonical_path());
+    assert_eq!(canonical, t!(fs::canonicalize(tmpdir.path())));
+    assert_eq!(canonical.file_name(), tmpdir.path().file_name());
+}
+
+#[cfg(unix)]
+pub fn test_canonical_path_symlink() {
+    use std::os::unix::fs::symlink;
+
+    t!(fs::create_dir("real"));
+    t!(symlink("real", "link"));
+    let tmpdir = t!(TempDir::new_in("link", "test_canonical_path"));
+    assert!(tmpdir.path().starts_with(t!(env::current_dir()).join("link")));
+    let canonical = t!(tmpdir.canonical_path());
+    assert!(canonical.starts_with(t!(fs::canonicalize("real"))));
+}
+
+#[cfg(not(unix))]
+pub fn test_canonical_path_symlink() {}
+
+pub fn test_max_name_len() {
+    let prefix = "p".repeat(250);
+    let err = TempDir::new_in(".", &prefix).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("263 bytes"));
+    assert!(err.to_string().contains("limit of 255"));
+    assert_eq!(t!(fs::read_dir(".")).count(), 0);
+
+    let mut builder = TempDir::builder();
+    builder.prefix("short").rand_bytes(4).max_name_len(10);
+    let dir = t!(builder.tempdir_in("."));
+    assert_eq!(dir.path().file_name().unwrap().len(), 10);
+    let err = builder.suffix("x").tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_lazy() {
+    let lazy = LazyTempDir::new_in(".", "test_lazy");
+    assert!(lazy.get().is_none());
+    drop(lazy);
+    assert_eq!(t!(fs::read_dir(".")).count(), 0);
+
+    let lazy = TempDir::builder().prefix("test_lazy").lazy_in(".");
+    let path = t!(lazy.path()).to_path_buf();
+    assert!(path.is_dir());
+    assert_eq!(t!(lazy.path()), &*path);
+    assert_eq!(t!(lazy.ensure()).path(), &*path);
+    assert_eq!(lazy.get().map(|d| d.path()), Some(&*path));
+    drop(lazy);
+    assert!(!path.exists());
+
+    let lazy = LazyTempDir::new_in(".", "test_lazy");
+    let dir = lazy.into_inner();
+    assert!(dir.is_none());
+
+    // Failures are not cached.
+    let lazy = LazyTempDir::new_in("later", "test_lazy");
+    assert_eq!(lazy.path().unwrap_err().kind(), ErrorKind::NotFound);
+    t!(fs::create_dir("later"));
+    assert!(t!(lazy.path()).is_dir());
+}
+
+#[cfg(unix)]
+pub fn test_set_permissions_recursive() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let outside = t!(TempDir::new("test_set_permissions_recursive"));
+    t!(fs::write(outside.path().join("target"), b"data"));
+    t!(fs::set_permissions(outside.path().join("target"), fs::Permissions::from_mode(0o600)));
+
+    let tmpdir = t!(TempDir::new("test_set_permissions_recursive"));
+    t!(fs::create_dir_all(tmpdir.path().join("a").join("b")));
+    t!(fs::write(tmpdir.path().join("a").join("b").join("file"), b"data"));
+    t!(fs::write(tmpdir.path().join("top"), b"data"));
+    t!(symlink(outside.path().join("target"), tmpdir.path().join("link")));
+
+    let mode = |p: &Path| t!(fs::symlink_metadata(p)).permissions().mode() & 0o777;
+    t!(tmpdir.set_permissions_recursive(0o755));
+    assert_eq!(mode(&tmpdir.path().join("a").join("b").join("file")), 0o755);
+    assert_eq!(mode(&tmpdir.path().join("a").join("b")), 0o755);
+    assert_eq!(mode(&outside.path().join("target")), 0o600);
+    assert_eq!(mode(tmpdir.path()), 0o700);
+
+    // Without search permission on directories the walk must still reach
+    // the files inside them.
+    t!(tmpdir.set_permissions_recursive(0o640));
+    assert_eq!(mode(&tmpdir.path().join("top")), 0o640);
+    assert_eq!(mode(&tmpdir.path().join("a")), 0o640);
+    t!(fs::set_permissions(tmpdir.path().join("a"), fs::Permissions::from_mode(0o700)));
+    t!(fs::set_permissions(tmpdir.path().join("a").join("b"), fs::Permissions::from_mode(0o700)));
+    assert_eq!(mode(&tmpdir.path().join("a").join("b").join("file")), 0o640);
+}
+
+#[cfg(not(unix))]
+pub fn test_set_permissions_recursive() {}
+
+pub fn test_close_timeout() {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    // Reports the directory as busy for the first `busy` removals.
+    #[derive(Clone, Default)]
+    struct BusyFs {
+        busy: Arc<Mutex<u32>>,
+        removals: Arc<Mutex<u32>>,
+    }
+
+    impl FsProvider for BusyFs {
+        fn create_dir(&self, path: &Path, _mode: u32) -> std::io::Result<()> {
+            fs::create_dir(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+            *self.removals.lock().unwrap() += 1;
+            let mut busy = self.busy.lock().unwrap();
+            if *busy > 0 {
+                *busy -= 1;
+                return Err(std::io::Error::new(ErrorKind::PermissionDenied, "busy"));
+            }
+            fs::remove_dir_all(path)
+        }
+    }
+
+    let dir = t!(TempDir::new_in(".", "close_timeout"));
+    let path = dir.path().to_path_buf();
+    t!(dir.close_timeout(Duration::from_secs(0)));
+    assert!(!path.exists());
+
+    let busy = BusyFs::default();
+    *busy.busy.lock().unwrap() = 3;
+    let dir = t!(TempDir::builder().prefix("busy").fs_provider(busy.clone()).tempdir_in("."));
+    let path = dir.path().to_path_buf();
+    t!(dir.close_timeout(Duration::from_secs(30)));
+    assert!(!path.exists());
+    assert_eq!(*busy.removals.lock().unwrap(), 4);
+
+    *busy.busy.lock().unwrap() = u32::MAX;
+    let dir = t!(TempDir::builder().prefix("busy").fs_provider(busy.clone()).tempdir_in("."));
+    let path = dir.path().to_path_buf();
+    let start = Instant::now();
+    let err = dir.close_timeout(Duration::from_millis(50)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(path.exists());
+    t!(fs::remove_dir(&path));
+}
+
+pub fn test_close_removes_once() {
+    use std::sync::{Arc, Mutex};
+
+    // Counts removals and fails them while `fail` is set.
+    #[derive(Clone, Default)]
+    struct CountingFs {
+        fail: Arc<Mutex<bool>>,
+        removals: Arc<Mutex<u32>>,
+    }
+
+    impl FsProvider for CountingFs {
+        fn create_dir(&self, path: &Path, _mode: u32) -> std::io::Result<()> {
+            fs::create_dir(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+            *self.removals.lock().unwrap() += 1;
+            if *self.fail.lock().unwrap() {
+                return Err(std::io::Error::new(ErrorKind::PermissionDenied, "busy"));
+            }
+            fs::remove_dir_all(path)
+        }
+    }
+
+    let counting = CountingFs::default();
+    let mut builder = TempDir::builder();
+    builder.prefix("once").fs_provider(counting.clone()).panic_on_cleanup_error(true);
+
+    t!(t!(builder.tempdir_in(".")).close());
+    assert_eq!(*counting.removals.lock().unwrap(), 1);
+    drop(t!(builder.tempdir_in(".")));
+    assert_eq!(*counting.removals.lock().unwrap(), 2);
+
+    *counting.fail.lock().unwrap() = true;
+    let dir = t!(builder.tempdir_in("."));
+    let path = dir.path().to_path_buf();
+    assert!(dir.close().is_err());
+    assert_eq!(*counting.removals.lock().unwrap(), 3);
+    t!(fs::remove_dir(&path));
+}
+
+pub fn test_into_parts() {
+    let dir = t!(TempDir::new("into_parts"));
+    let (path, created) = dir.into_parts();
+    assert!(created);
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+
+    t!(fs::create_dir("adopted"));
+    let dir = t!(TempDir::from_existing_path("adopted".into()));
+    let (path, created) = dir.into_parts();
+    assert!(!created);
+    assert!(path.is_dir());
+    assert_eq!(path, t!(env::current_dir()).join("adopted"));
+}
+
+#[cfg(unix)]
+pub fn test_symlink() {
+    let outside = t!(TempDir::new("test_symlink_outside"));
+    let tmp = t!(TempDir::new("test_symlink"));
+
+    let link = t!(tmp.symlink(outside.path(), "a/b/link"));
+    assert_eq!(link, tmp.path().join("a/b/link"));
+    assert_eq!(t!(fs::read_link(&link)), outside.path());
+    assert!(link.is_dir());
+
+    let dangling = t!(tmp.symlink("missing", "dangling"));
+    assert_eq!(t!(fs::read_link(&dangling)), Path::new("missing"));
+    assert!(!dangling.exists());
+
+    let err = tmp.symlink("missing", "dangling").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    let err = tmp.symlink("missing", "../escape").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[cfg(not(unix))]
+pub fn test_symlink() {}
+
+pub fn test_bases() {
+    let cwd = t!(env::current_dir());
+    let bases = vec![cwd.join("a"), cwd.join("b"), cwd.join("c")];
+    for base in &bases {
+        t!(fs::create_dir(base));
+    }
+
+    let mut builder = TempDir::builder();
+    builder.prefix("bases").bases(bases.clone());
+    let mut seen = Vec::new();
+    for _ in 0..64 {
+        let dir = t!(builder.tempdir());
+        let base = dir.base().to_path_buf();
+        assert!(bases.contains(&base));
+        assert!(dir.path().starts_with(&base));
+        if !seen.contains(&base) {
+            seen.push(base);
+        }
+    }
+    assert_eq!(seen.len(), bases.len());
+
+    // An explicit base wins.
+    let dir = t!(builder.tempdir_in("."));
+    assert_eq!(dir.base(), &*cwd);
+
+    let mut builder = TempDir::builder();
+    builder.bases(vec![cwd.join("missing"), cwd.join("b")]);
+    let mut failed = false;
+    for _ in 0..64 {
+        if let Err(e) = builder.tempdir() {
+            assert_eq!(e.kind(), ErrorKind::NotFound);
+            failed = true;
+        }
+    }
+    assert!(failed);
+
+    builder.try_next_base(true);
+    for _ in 0..16 {
+        let dir = t!(builder.tempdir());
+        assert_eq!(dir.base(), cwd.join("b"));
+    }
+
+    builder.bases(vec![cwd.join("missing")]);
+    assert_eq!(builder.tempdir().unwrap_err().kind(), ErrorKind::NotFound);
+    builder.bases(vec![cwd.join("b")]).prefix("a/b");
+    match builder.try_tempdir() {
+        Err(TempDirError::InvalidName(_)) => {}
+        other => panic!("expected an invalid name, got {:?}", other),
+    }
+}
+
+pub fn test_close_verbose() {
+    let dir = t!(TempDir::new_in(".", "close_verbose"));
+    let path = dir.path().to_path_buf();
+    t!(dir.create_file("a/b/c"));
+    t!(dir.create_file("a/d"));
+    t!(dir.create_file("e"));
+    dir.close_verbose().unwrap();
+    assert!(!path.exists());
+
+    let dir = t!(TempDir::new_in(".", "close_verbose"));
+    let path = dir.path().to_path_buf();
+    t!(fs::remove_dir(&path));
+    let failures = dir.close_verbose().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, path);
+    assert_eq!(failures[0].1.kind(), ErrorKind::NotFound);
+
+    let mut dir = t!(TempDir::new_in(".", "close_verbose"));
+    let path = dir.path().to_path_buf();
+    dir.disable_cleanup();
+    dir.close_verbose().unwrap();
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+}
+
+#[cfg(unix)]
+pub fn test_close_verbose_locked() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = t!(TempDir::new_in(".", "close_verbose_locked"));
+    let path = dir.path().to_path_buf();
+    let locked = path.join("locked");
+    let file = t!(dir.create_file("locked/file"));
+    t!(dir.create_file("free/file"));
+    t!(fs::set_permissions(&locked, fs::Permissions::from_mode(0o500)));
+
+    match dir.close_verbose() {
+        // Privileged users can remove the file anyway.
+        Ok(()) => assert!(!path.exists()),
+        Err(failures) => {
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].0, file);
+            assert_eq!(failures[0].1.kind(), ErrorKind::PermissionDenied);
+            assert!(!path.join("free").exists());
+            t!(fs::set_permissions(&locked, fs::Permissions::from_mode(0o700)));
+            t!(fs::remove_dir_all(&path));
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn test_close_verbose_locked() {}
+
+pub fn test_template() {
+    let dir = t!(TempDir::builder().template("build-{}-tmp").rand_bytes(8).tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("build-"));
+    assert!(name.ends_with("-tmp"));
+    assert_eq!(name.len(), "build--tmp".len() + 8);
+
+    let dir = t!(TempDir::builder()
+        .template("plain-")
+        .prefix("ignored")
+        .suffix(".ignored")
+        .rand_bytes(4)
+        .tempdir_in("."));
+    let name = dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("plain-"));
+    assert_eq!(name.len(), "plain-".len() + 4);
+    assert_eq!(dir.prefix(), "ignored");
+
+    let dir = t!(TempDir::builder().template("{}").rand_bytes(4).tempdir_in("."));
+    assert_eq!(dir.path().file_name().unwrap().len(), 4);
+
+    let dir = t!(TempDir::builder().template("fixed").rand_bytes(0).tempdir_in("."));
+    assert_eq!(dir.path().file_name().unwrap(), "fixed");
+
+    for bad in &["a-{}-{}", "a/{}", "{}\0", "{}"] {
+        let mut builder = TempDir::builder();
+        builder.template(bad).rand_bytes(if *bad == "{}" { 0 } else { 4 });
+        let err = builder.tempdir_in(".").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
+
+pub fn test_metadata() {
+    let dir = t!(TempDir::new_in(".", "metadata"));
+    let metadata = t!(dir.metadata());
+    assert!(metadata.is_dir());
+    assert_eq!(metadata.len(), t!(fs::metadata(dir.path())).len());
+
+    t!(fs::remove_dir(dir.path()));
+    assert_eq!(dir.metadata().unwrap_err().kind(), ErrorKind::NotFound);
+    dir.into_path();
+}
+
+pub fn test_plan_name() {
+    let mut builder = TempDir::builder();
+    builder.prefix("plan").suffix(".tmp").rand_bytes(10);
+    let name = builder.plan_name();
+    assert!(name.starts_with("plan."));
+    assert!(name.ends_with(".tmp"));
+    assert_eq!(name.len(), "plan..tmp".len() + 10);
+    assert!(t!(fs::read_dir(".")).next().is_none());
+
+    builder.template("x-{}-y").rand_alphabet(&['q']).rand_bytes(3);
+    assert_eq!(builder.plan_name(), "x-qqq-y");
+
+    let mut builder = TempDir::builder();
+    builder.prefix("seq").naming(Naming::Sequential);
+    assert_eq!(builder.plan_name(), "seq.0");
+    let dir = t!(builder.tempdir_in("."));
+    assert_eq!(dir.path().file_name().unwrap(), &*builder.plan_name());
+}
+
+pub fn test_keep() {
+    // Leaves its scratch directory behind only when `check` fails.
+    fn run(check: bool) -> PathBuf {
+        let mut scratch = t!(TempDir::new_in(".", "keep"));
+        t!(scratch.create_file("artifact"));
+        if !check {
+            scratch.keep();
+        }
+        scratch.path().to_path_buf()
+    }
+
+    let path = run(true);
+    assert!(!path.exists());
+
+    let path = run(false);
+    assert!(path.join("artifact").is_file());
+    t!(fs::remove_dir_all(&path));
+}
+
+pub fn test_content_hash() {
+    let a = t!(TempDir::new_in(".", "hash"));
+    let b = t!(TempDir::new_in(".", "hash"));
+    assert_eq!(t!(a.content_hash()), t!(b.content_hash()));
+
+    // Creation order doesn't matter.
+    t!(fs::write(a.path().join("x"), "one"));
+    t!(fs::create_dir_all(a.path().join("sub/empty")));
+    t!(fs::write(a.path().join("sub/y"), "two"));
+    t!(fs::create_dir_all(b.path().join("sub/empty")));
+    t!(fs::write(b.path().join("sub/y"), "two"));
+    t!(fs::write(b.path().join("x"), "one"));
+    let hash = t!(a.content_hash());
+    assert_eq!(hash, t!(b.content_hash()));
+    assert_eq!(hash, t!(a.content_hash()));
+
+    t!(fs::write(b.path().join("x"), "One"));
+    assert!(t!(b.content_hash()) != hash);
+    t!(fs::write(b.path().join("x"), "one"));
+    t!(fs::rename(b.path().join("sub/y"), b.path().join("sub/z")));
+    assert!(t!(b.content_hash()) != hash);
+    t!(fs::rename(b.path().join("sub/z"), b.path().join("sub/y")));
+    t!(fs::remove_dir(b.path().join("sub/empty")));
+    assert!(t!(b.content_hash()) != hash);
+    t!(fs::create_dir(b.path().join("sub/empty")));
+    assert_eq!(t!(b.content_hash()), hash);
+
+    // Moving bytes between a name and the contents changes the digest.
+    let c = t!(TempDir::new_in(".", "hash"));
+    let d = t!(TempDir::new_in(".", "hash"));
+    t!(fs::write(c.path().join("ab"), "c"));
+    t!(fs::write(d.path().join("a"), "bc"));
+    assert!(t!(c.content_hash()) != t!(d.content_hash()));
+}
+
+#[cfg(unix)]
+pub fn test_content_hash_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let outside = t!(TempDir::new_in(".", "hash_outside"));
+    t!(fs::write(outside.path().join("target"), "before"));
+    let dir = t!(TempDir::new_in(".", "hash"));
+    t!(symlink(outside.path().join("target"), dir.path().join("link")));
+    let hash = t!(dir.content_hash());
+
+    // The link, not its target, is hashed.
+    t!(fs::write(outside.path().join("target"), "after"));
+    assert_eq!(t!(dir.content_hash()), hash);
+    t!(fs::remove_file(dir.path().join("link")));
+    t!(symlink(outside.path().join("other"), dir.path().join("link")));
+    assert!(t!(dir.content_hash()) != hash);
+}
+
+#[cfg(not(unix))]
+pub fn test_content_hash_symlinks() {}
+
+#[cfg(target_os = "linux")]
+pub fn test_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cwd = t!(env::current_dir());
+    let mut builder = TempDir::builder();
+    builder.prefix("private").private(true);
+    let first = t!(builder.tempdir_in("."));
+    let second = t!(builder.tempdir_in("."));
+    let parent = first.base().to_path_buf();
+    assert_eq!(second.base(), &*parent);
+    assert_eq!(parent.parent(), Some(&*cwd));
+    let name = parent.file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with(&format!("tempdir-private.{}.", std::process::id())));
+    assert_eq!(t!(fs::metadata(&parent)).permissions().mode() & 0o777, 0o700);
+    drop(first);
+    drop(second);
+
+    // A swept parent is replaced.
+    t!(fs::remove_dir(&parent));
+    let third = t!(builder.tempdir_in("."));
+    assert!(third.base() != &*parent);
+    assert_eq!(third.base().parent(), Some(&*cwd));
+
+    let plain = t!(TempDir::builder().prefix("private").tempdir_in("."));
+    assert_eq!(plain.base(), &*cwd);
+
+    // Looking for a name for the parent is bounded by `max_retries`.
+    t!(fs::create_dir("fresh"));
+    match builder.max_retries(0).try_tempdir_in("fresh") {
+        Err(TempDirError::Exhausted { attempts }) => assert_eq!(attempts, 0),
+        other => panic!("expected the name search to be exhausted, got {:?}", other),
+    }
+    assert_eq!(t!(fs::read_dir("fresh")).count(), 0);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn test_private() {
+    let cwd = t!(env::current_dir());
+    let dir = t!(TempDir::builder().prefix("private").private(true).tempdir_in("."));
+    assert_eq!(dir.base(), &*cwd);
+}
+
+pub fn test_list_files() {
+    let dir = t!(TempDir::new_in(".", "list_files"));
+    assert!(t!(dir.list_files()).is_empty());
+
+    t!(dir.create_file("z"));
+    t!(dir.create_file("a/b/c"));
+    t!(dir.create_file("a/d"));
+    t!(fs::create_dir(dir.path().join("empty")));
+    assert_eq!(t!(dir.list_files()),
+               [Path::new("a").join("b").join("c"), Path::new("a").join("d"), PathBuf::from("z")]);
+
+    t!(fs::remove_dir_all(dir.path()));
+    assert_eq!(dir.list_files().unwrap_err().kind(), ErrorKind::NotFound);
+    dir.into_path();
+}
+
+#[cfg(unix)]
+pub fn test_list_files_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let dir = t!(TempDir::new_in(".", "list_files"));
+    t!(dir.create_file("file"));
+    t!(symlink(dir.path().join("file"), dir.path().join("link")));
+    t!(symlink(dir.path(), dir.path().join("loop")));
+    assert_eq!(t!(dir.list_files()), [PathBuf::from("file")]);
+}
+
+#[cfg(not(unix))]
+pub fn test_list_files_symlinks() {}
+
+pub fn test_collision_backoff() {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    // Claims the first `collisions` names are taken, then fails with
+    // `error` if set.
+    #[derive(Clone, Default)]
+    struct ContendedFs {
+        collisions: Arc<Mutex<u32>>,
+        error: Arc<Mutex<Option<ErrorKind>>>,
+    }
+
+    impl FsProvider for ContendedFs {
+        fn create_dir(&self, _path: &Path, _mode: u32) -> std::io::Result<()> {
+            let mut collisions = self.collisions.lock().unwrap();
+            if *collisions > 0 {
+                *collisions -= 1;
+                return Err(std::io::Error::new(ErrorKind::AlreadyExists, "taken"));
+            }
+            match *self.error.lock().unwrap() {
+                Some(kind) => Err(std::io::Error::new(kind, "failed")),
+                None => Ok(()),
+            }
+        }
+
+        fn remove_dir_all(&self, _path: &Path) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let fs = ContendedFs::default();
+    let mut builder = TempDir::builder();
+    builder.fs_provider(fs.clone()).collision_backoff(Duration::from_millis(20));
+
+    *fs.collisions.lock().unwrap() = 5;
+    let start = Instant::now();
+    let dir = t!(builder.tempdir_in("."));
+    assert_eq!(dir.attempts(), 6);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // Other errors don't wait.
+    *fs.error.lock().unwrap() = Some(ErrorKind::PermissionDenied);
+    builder.collision_backoff(Duration::from_secs(60));
+    let start = Instant::now();
+    let err = builder.tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // Nor does giving up.
+    *fs.collisions.lock().unwrap() = 1;
+    let start = Instant::now();
+    let err = builder.max_retries(1).tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+pub fn test_new_file() {
+    let dir = t!(TempDir::new_in(".", "new_file"));
+    let (path, mut file) = t!(dir.new_file("data"));
+    assert_eq!(path, dir.path().join("data"));
+    t!(file.write_all(b"first"));
+    drop(file);
+
+    let err = dir.new_file("data").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    let mut contents = String::new();
+    t!(t!(fs::File::open(&path)).read_to_string(&mut contents));
+    assert_eq!(contents, "first");
+
+    for bad in &["", ".", "..", "a/b", "../escape", "/absolute", "nul\0"] {
+        let err = dir.new_file(bad).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    // Names leaving the directory are caught by the same check as
+    // `try_child`, so they report the same error.
+    for bad in &["..", "../escape", "/absolute"] {
+        let err = dir.new_file(bad).unwrap_err();
+        assert_eq!(err.to_string(), dir.try_child(bad).unwrap_err().to_string());
+    }
+    let err = dir.new_file("a/b").unwrap_err();
+    assert_eq!(err.to_string(), "`a/b` is not a valid file name");
+    assert_eq!(t!(dir.list_files()), [PathBuf::from("data")]);
+}
+
+pub fn test_max_depth() {
+    let mut builder = TempDir::builder();
+    builder.prefix("depth").max_depth(3);
+
+    let dir = t!(builder.tempdir_in("."));
+    t!(dir.create_file("a/b/c"));
+    assert_eq!(t!(dir.size()), 0);
+    assert_eq!(t!(dir.entry_count()), 3);
+    assert_eq!(t!(dir.list_files()).len(), 1);
+    t!(dir.content_hash());
+
+    t!(fs::create_dir(dir.path().join("a/b/d")));
+    assert_eq!(t!(dir.entry_count()), 4);
+    t!(dir.create_file("a/b/d/e"));
+    assert_eq!(dir.size().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(dir.entry_count().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(dir.list_files().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(dir.content_hash().unwrap_err().kind(), ErrorKind::InvalidData);
+    let copy = t!(builder.tempdir_in("."));
+    assert_eq!(copy.copy_from(dir.path()).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(dir.clear().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert!(dir.path().join("a/b/d/e").exists());
+
+    // The limit is inherited by children.
+    let child = t!(dir.new_child("child"));
+    t!(child.create_file("a/b/c/d"));
+    assert_eq!(child.size().unwrap_err().kind(), ErrorKind::InvalidData);
+    drop(child);
+
+    let path = dir.path().to_path_buf();
+    let failures = dir.close_verbose().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, path.join("a/b/d"));
+    assert_eq!(failures[0].1.kind(), ErrorKind::InvalidData);
+    assert!(path.join("a/b/d/e").exists());
+    assert!(!path.join("a/b/c").exists());
+    t!(fs::remove_dir_all(&path));
+
+    let deep = t!(TempDir::new_in(".", "depth"));
+    let mut rel = PathBuf::new();
+    for _ in 0..64 {
+        rel.push("x");
+    }
+    t!(deep.create_file(rel.join("file")));
+    assert_eq!(t!(deep.entry_count()), 65);
+}
+
+pub fn test_name_fn() {
+    let mut next = 0;
+    let mut builder = TempDir::builder();
+    builder.prefix("ignored").suffix(".ignored").name_fn(move || {
+        next += 1;
+        format!("custom-{}", next / 2)
+    });
+    let first = t!(builder.tempdir_in("."));
+    assert_eq!(first.path().file_name().unwrap(), "custom-0");
+    assert_eq!(first.attempts(), 1);
+    let second = t!(builder.tempdir_in("."));
+    assert_eq!(second.path().file_name().unwrap(), "custom-1");
+    // "custom-1" comes up again, so it is retried.
+    let third = t!(builder.tempdir_in("."));
+    assert_eq!(third.path().file_name().unwrap(), "custom-2");
+    assert_eq!(third.attempts(), 2);
+    // Planning doesn't check whether the name is taken.
+    assert_eq!(builder.plan_name(), "custom-2");
+
+    let mut builder = TempDir::builder();
+    builder.name_fn(|| "same".to_owned()).max_retries(3);
+    let same = t!(builder.tempdir_in("."));
+    match builder.try_tempdir_in(".") {
+        Err(TempDirError::Exhausted { attempts }) => assert_eq!(attempts, 3),
+        other => panic!("expected exhaustion, got {:?}", other),
+    }
+    drop(same);
+
+    for bad in &["", ".", "..", "a/b", "nul\0"] {
+        let err = TempDir::builder().name_fn(move || bad.to_string()).tempdir_in(".").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
+
+pub fn test_contains() {
+    let dir = t!(TempDir::new_in(".", "contains"));
+    let path = dir.path();
+    assert!(dir.contains(path));
+    assert!(dir.contains(path.join("a/b")));
+    assert!(dir.contains(path.join("a/../b")));
+    assert!(dir.contains(path.join("./a/.")));
+    assert!(dir.contains(format!("{}/", path.display())));
+    assert!(!dir.contains(path.join("..")));
+    assert!(!dir.contains(path.join("a/../../b")));
+    assert!(!dir.contains(path.parent().unwrap()));
+    assert!(!dir.contains(format!("{}-sibling", path.display())));
+    assert!(!dir.contains("a/b"));
+    assert!(!dir.contains(""));
+
+    t!(fs::create_dir("x"));
+    let relative_base = t!(TempDir::new_in("./x/..", "contains"));
+    assert!(relative_base.contains(t!(env::current_dir())
+        .join(relative_base.path().file_name().unwrap())
+        .join("file")));
+}
+
+pub fn test_commit_file() {
+    let dir = t!(TempDir::new_in(".", "commit_file"));
+    t!(fs::write(t!(dir.create_file("out/data")), "new"));
+    t!(fs::write("dest", "old"));
+    t!(dir.commit_file("out/data", "dest"));
+    assert!(!dir.path().join("out/data").exists());
+    let mut contents = String::new();
+    t!(t!(fs::File::open("dest")).read_to_string(&mut contents));
+    assert_eq!(contents, "new");
+
+    let err = dir.commit_file("out/data", "dest").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let err = dir.commit_file("out", "dest").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    t!(fs::write("outside", "precious"));
+    let err = dir.commit_file("../outside", "dest").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(Path::new("outside").exists());
+}
+
+pub fn test_tempdir_in_first() {
+    let cwd = t!(env::current_dir());
+    t!(fs::create_dir("fast"));
+    t!(fs::create_dir("slow"));
+    let mut builder = TempDir::builder();
+    builder.prefix("first");
+
+    let dir = t!(builder.tempdir_in_first(["missing", "fast", "slow"], 0));
+    assert_eq!(dir.base(), cwd.join("fast"));
+
+    // No file system has this much space, so every candidate is skipped.
+    let err = builder.tempdir_in_first(["fast", "slow"], u64::MAX).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    let message = err.to_string();
+    assert!(message.contains("fast"), "{}", message);
+    assert!(message.contains("slow"), "{}", message);
+
+    if t!(dir.available_space()) > 0 {
+        let dir = t!(builder.tempdir_in_first(["missing", "slow"], 1));
+        assert_eq!(dir.base(), cwd.join("slow"));
+    }
+
+    let none: &[&str] = &[];
+    assert_eq!(builder.tempdir_in_first(none, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    let err = builder.prefix("a/b").tempdir_in_first(["fast"], 0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+pub fn test_exhaustion() {
+    t!(fs::create_dir("taken"));
+    let mut builder = TempDir::builder();
+    builder.name_fn(|| "taken".to_owned()).max_retries(4);
+
+    let err = builder.tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert_eq!(err.to_string(),
+               "too many temporary directories already exist (gave up after 4 attempts)");
+    match builder.try_tempdir_in(".") {
+        Err(TempDirError::Exhausted { attempts }) => assert_eq!(attempts, 4),
+        other => panic!("expected exhaustion, got {:?}", other),
+    }
+    assert_eq!(t!(fs::read_dir(".")).count(), 1);
+}
+
+pub fn test_into_path_with_cleanup() {
+    let dir = t!(TempDir::new_in(".", "with_cleanup"));
+    let (path, cleanup) = dir.into_path_with_cleanup();
+    t!(fs::write(path.join("file"), "kept"));
+    assert!(path.is_dir());
+    t!(cleanup());
+    assert!(!path.exists());
+
+    // Dropping the closure leaves the directory alone.
+    let dir = t!(TempDir::new_in(".", "with_cleanup"));
+    let (path, cleanup) = dir.into_path_with_cleanup();
+    drop(cleanup);
+    assert!(path.is_dir());
+    t!(fs::remove_dir(&path));
+
+    let dir = t!(TempDir::new_in(".", "with_cleanup"));
+    let (path, cleanup) = dir.into_path_with_cleanup();
+    t!(fs::remove_dir(&path));
+    assert_eq!(cleanup().unwrap_err().kind(), ErrorKind::NotFound);
+
+    let mut dir = t!(TempDir::new_in(".", "with_cleanup"));
+    dir.disable_cleanup();
+    let (path, cleanup) = dir.into_path_with_cleanup();
+    t!(cleanup());
+    assert!(path.is_dir());
+}
+
+#[cfg(unix)]
+pub fn test_restrict_to_user() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = t!(TempDir::builder().prefix("test_restrict_to_user").mode(0o755)
+                     .restrict_to_user(true).tempdir());
+    let mode = t!(fs::metadata(tmp.path())).permissions().mode();
+    assert_eq!(mode & 0o777 & !0o700, 0);
+    assert_eq!(mode & 0o700, 0o700);
+}
+
+#[cfg(not(unix))]
+pub fn test_restrict_to_user() {}
+
+pub fn test_new_batch_in() {
+    let dirs = t!(TempDir::new_batch_in(".", "test_new_batch_in", 3));
+    assert_eq!(dirs.len(), 3);
+    let paths: Vec<PathBuf> = dirs.iter().map(|d| d.path().to_path_buf()).collect();
+    for (i, path) in paths.iter().enumerate() {
+        assert!(path.is_dir());
+        assert!(!paths[i + 1..].contains(path));
+    }
+    drop(dirs);
+    assert!(paths.iter().all(|p| !p.exists()));
+
+    assert!(t!(TempDir::new_batch_in(".", "test_new_batch_in", 0)).is_empty());
+
+    let err = TempDir::new_batch_in(".", "bad/prefix", 2).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    // The third directory fails, so the first two are removed again.
+    struct FailThird(std::sync::Mutex<u32>);
+
+    impl FsProvider for FailThird {
+        fn create_dir(&self, path: &Path, _mode: u32) -> std::io::Result<()> {
+            let mut created = self.0.lock().unwrap();
+            if *created == 2 {
+                return Err(std::io::Error::new(ErrorKind::PermissionDenied, "denied"));
+            }
+            *created += 1;
+            fs::create_dir(path)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+            fs::remove_dir_all(path)
+        }
+    }
+
+    let err = TempDir::builder()
+        .prefix("test_new_batch_in")
+        .fs_provider(FailThird(std::sync::Mutex::new(0)))
+        .tempdir_batch_in(".", 4)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(t!(fs::read_dir(".")).count(), 0);
+}
+
+pub fn test_file_id() {
+    let tmp = t!(TempDir::new_in(".", "test_file_id"));
+    let id = t!(tmp.file_id());
+    assert_eq!(t!(tmp.file_id()), id);
+    let other = t!(TempDir::new_in(".", "test_file_id"));
+    assert!(t!(other.file_id()) != id);
+
+    // Replacing the directory changes its identity. The stand-in is
+    // created before the original is removed so its ID can't be reused.
+    let path = tmp.path().to_path_buf();
+    t!(fs::create_dir("test_file_id_replacement"));
+    t!(fs::remove_dir(&path));
+    t!(fs::rename("test_file_id_replacement", &path));
+    assert!(t!(tmp.file_id()) != id);
+
+    t!(fs::remove_dir(&path));
+    assert_eq!(tmp.file_id().unwrap_err().kind(), ErrorKind::NotFound);
+    t!(fs::create_dir(&path));
+}
+
+pub fn test_sanitize_prefix() {
+    let err = TempDir::builder().prefix("a/b").tempdir_in(".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let tmp = t!(TempDir::builder().prefix("a/b\\c\0d:e\nf").sanitize_prefix(true)
+                     .tempdir_in("."));
+    assert_eq!(tmp.prefix(), "a_b_c_d_e_f");
+    // Still a single directory right inside the base.
+    assert_eq!(t!(fs::read_dir(".")).count(), 1);
+    let name = tmp.path().file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("a_b_c_d_e_f."));
+
+    let tmp = t!(TempDir::builder().prefix("plain name").sanitize_prefix(true).tempdir_in("."));
+    assert_eq!(tmp.prefix(), "plain name");
+}
+
+pub fn test_reserve_layout() {
+    let tmp = t!(TempDir::new_in(".", "test_reserve_layout"));
+    t!(tmp.reserve_layout(3, 2));
+    assert_eq!(t!(tmp.read_dir()).count(), 3);
+    for d in 0..3 {
+        let dir = tmp.path().join(format!("dir{}", d));
+        assert_eq!(t!(fs::read_dir(&dir)).count(), 2);
+        for f in 0..2 {
+            assert_eq!(t!(fs::metadata(dir.join(format!("file{}", f)))).len(), 0);
+        }
+    }
+
+    // Directories without files, and nothing at all.
+    let empty = t!(TempDir::new_in(".", "test_reserve_layout"));
+    t!(empty.reserve_layout(2, 0));
+    assert_eq!(t!(empty.read_dir()).count(), 2);
+    assert!(t!(empty.list_files()).is_empty());
+    let none = t!(TempDir::new_in(".", "test_reserve_layout"));
+    t!(none.reserve_layout(0, 5));
+    assert!(t!(none.is_empty()));
+
+    // An existing entry stops it with the first error.
+    let err = tmp.reserve_layout(1, 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+}
+
+pub fn test_debug() {
+    let mut tmp = t!(TempDir::new_in(".", "test_debug"));
+    let debug = format!("{:?}", tmp);
+    assert!(debug.starts_with(&format!("TempDir {{ path: {:?}, ", tmp.path())));
+    assert!(debug.contains("cleanup: true"));
+    assert!(debug.contains("created: true"));
+
+    tmp.disable_cleanup();
+    assert!(format!("{:?}", tmp).contains("cleanup: false"));
+    t!(tmp.close());
+
+    t!(fs::create_dir("test_debug_existing"));
+    let adopted = t!(TempDir::from_existing_path(PathBuf::from("test_debug_existing")));
+    assert!(format!("{:?}", adopted).contains("created: false"));
+}
+
+pub fn test_sync_all() {
+    let tmp = t!(TempDir::new_in(".", "test_sync_all"));
+    t!(tmp.sync_all());
+
+    t!(fs::write(tmp.path().join("a"), b"one"));
+    t!(fs::create_dir_all(tmp.path().join("b/c")));
+    t!(fs::write(tmp.path().join("b/c/d"), b"two"));
+    t!(tmp.sync_all());
+    assert_eq!(t!(fs::read(tmp.path().join("b/c/d"))), b"two");
+
+    let path = tmp.path().to_path_buf();
+    t!(fs::remove_dir_all(&path));
+    assert_eq!(tmp.sync_all().unwrap_err().kind(), ErrorKind::NotFound);
+    t!(fs::create_dir(&path));
+}
+
+pub fn test_cleanup_mode() {
+    use std::sync::{Arc, Mutex};
+
+    // An empty directory is removed as usual.
+    let tmp = t!(TempDir::builder().prefix("test_cleanup_mode")
+                     .cleanup_mode(CleanupMode::Empty).tempdir_in("."));
+    let path = tmp.path().to_path_buf();
+    t!(tmp.close());
+    assert!(!path.exists());
+
+    // Leftovers make `close` fail and keep everything.
+    let tmp = t!(TempDir::builder().prefix("test_cleanup_mode")
+                     .cleanup_mode(CleanupMode::Empty).tempdir_in("."));
+    let path = tmp.path().to_path_buf();
+    t!(tmp.create_file("leftover"));
+    assert_eq!(tmp.close().unwrap_err().kind(), ErrorKind::DirectoryNotEmpty);
+    assert!(path.join("leftover").exists());
+    t!(fs::remove_dir_all(&path));
+
+    // On drop they are reported to the error handler.
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = reported.clone();
+    let tmp = t!(TempDir::builder()
+        .prefix("test_cleanup_mode")
+        .cleanup_mode(CleanupMode::Empty)
+        .on_cleanup_error(move |path, e| sink.lock().unwrap().push((path.to_path_buf(), e.kind())))
+        .tempdir_in("."));
+    let path = tmp.path().to_path_buf();
+    t!(tmp.create_file("leftover"));
+    drop(tmp);
+    assert_eq!(*reported.lock().unwrap(), vec![(path.clone(), ErrorKind::DirectoryNotEmpty)]);
+    assert!(path.join("leftover").exists());
+    t!(fs::remove_dir_all(&path));
+
+    let tmp = t!(TempDir::builder().prefix("test_cleanup_mode")
+                     .cleanup_mode(CleanupMode::Empty).tempdir_in("."));
+    t!(tmp.create_file("leftover"));
+    let failures = tmp.close_verbose().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].1.kind(), ErrorKind::DirectoryNotEmpty);
+    t!(fs::remove_dir_all(&failures[0].0));
+
+    // The default still removes everything.
+    let tmp = t!(TempDir::builder().prefix("test_cleanup_mode")
+                     .cleanup_mode(CleanupMode::Recursive).tempdir_in("."));
+    let path = tmp.path().to_path_buf();
+    t!(tmp.create_file("a/b"));
+    t!(tmp.close());
+    assert!(!path.exists());
+    assert_eq!(CleanupMode::default(), CleanupMode::Recursive);
 }
 
 #[test]
@@ -226,4 +2375,106 @@ fn main() {
     in_tmpdir(test_remove_dir_all_ok);
     in_tmpdir(dont_double_panic);
     in_tmpdir(pass_as_asref_path);
+    in_tmpdir(test_builder);
+    in_tmpdir(test_suffix);
+    in_tmpdir(test_rand_bytes);
+    in_tmpdir(test_max_retries);
+    in_tmpdir(test_custom_rng);
+    in_tmpdir(test_reused_builder);
+    in_tmpdir(test_deref_path);
+    in_tmpdir(test_child);
+    in_tmpdir(test_disable_cleanup);
+    in_tmpdir(test_keep_on_panic);
+    in_tmpdir(test_mode);
+    in_tmpdir(test_from_existing_path);
+    in_tmpdir(test_display);
+    in_tmpdir(test_create_parents);
+    in_tmpdir(test_tempfile);
+    in_tmpdir(test_close_path);
+    in_tmpdir(test_is_empty);
+    in_tmpdir(test_size);
+    in_tmpdir(test_entry_count);
+    in_tmpdir(test_cleanup_keeps_symlink_targets);
+    in_tmpdir(test_new_named_in);
+    in_tmpdir(test_prefix_and_base);
+    in_tmpdir(test_deserialize);
+    in_tmpdir(test_sequential_naming);
+    in_tmpdir(test_include_pid);
+    in_tmpdir(test_invalid_prefix);
+    in_tmpdir(test_enter);
+    in_tmpdir(test_copy_from);
+    in_tmpdir(test_copy_from_symlinks);
+    in_tmpdir(test_eq_hash);
+    in_tmpdir(test_new_in_tmpdir_env);
+    in_tmpdir(test_close_async);
+    in_tmpdir(test_on_cleanup_error);
+    in_tmpdir(test_panic_on_cleanup_error);
+    in_tmpdir(test_random_name);
+    in_tmpdir(test_new_child);
+    in_tmpdir(test_close_if_empty);
+    in_tmpdir(test_tempdir_in_namespace);
+    in_tmpdir(test_into_path_synced);
+    in_tmpdir(test_ord);
+    in_tmpdir(test_long_path);
+    in_tmpdir(test_rand_alphabet);
+    in_tmpdir(test_attempts);
+    in_tmpdir(test_persist_to);
+    in_tmpdir(test_separator);
+    in_tmpdir(test_separator_with_pid);
+    in_tmpdir(test_try_from);
+    in_tmpdir(test_clear);
+    in_tmpdir(test_clear_symlinks);
+    in_tmpdir(test_typed_errors);
+    in_tmpdir(test_read_dir);
+    in_tmpdir(test_setgid);
+    in_tmpdir(test_available_space);
+    in_tmpdir(test_tempdir_set);
+    in_tmpdir(test_dot_without_prefix);
+    in_tmpdir(test_fs_provider);
+    in_tmpdir(test_create_file);
+    in_tmpdir(test_try_child);
+    in_tmpdir(test_close_owned);
+    in_tmpdir(test_path_str);
+    in_tmpdir(test_path_str_non_utf8);
+    in_tmpdir(test_require_sticky);
+    in_tmpdir(test_chown);
+    in_tmpdir(test_check_base);
+    in_tmpdir(test_canonical_path);
+    in_tmpdir(test_canonical_path_symlink);
+    in_tmpdir(test_max_name_len);
+    in_tmpdir(test_lazy);
+    in_tmpdir(test_set_permissions_recursive);
+    in_tmpdir(test_close_timeout);
+    in_tmpdir(test_close_removes_once);
+    in_tmpdir(test_into_parts);
+    in_tmpdir(test_symlink);
+    in_tmpdir(test_bases);
+    in_tmpdir(test_close_verbose);
+    in_tmpdir(test_close_verbose_locked);
+    in_tmpdir(test_template);
+    in_tmpdir(test_metadata);
+    in_tmpdir(test_plan_name);
+    in_tmpdir(test_keep);
+    in_tmpdir(test_content_hash);
+    in_tmpdir(test_content_hash_symlinks);
+    in_tmpdir(test_private);
+    in_tmpdir(test_list_files);
+    in_tmpdir(test_list_files_symlinks);
+    in_tmpdir(test_collision_backoff);
+    in_tmpdir(test_new_file);
+    in_tmpdir(test_max_depth);
+    in_tmpdir(test_name_fn);
+    in_tmpdir(test_contains);
+    in_tmpdir(test_commit_file);
+    in_tmpdir(test_tempdir_in_first);
+    in_tmpdir(test_exhaustion);
+    in_tmpdir(test_into_path_with_cleanup);
+    in_tmpdir(test_restrict_to_user);
+    in_tmpdir(test_new_batch_in);
+    in_tmpdir(test_file_id);
+    in_tmpdir(test_sanitize_prefix);
+    in_tmpdir(test_reserve_layout);
+    in_tmpdir(test_debug);
+    in_tmpdir(test_sync_all);
+    in_tmpdir(test_cleanup_mode);
 }